@@ -67,6 +67,8 @@ impl support::Texture for TextureId {
 fn rotate(degrees: f32) -> alphabet::Transform {
   alphabet::Transform {
     rotation : std::f32::consts::PI * degrees / 180.0,
+    pitch    : 0.0,
+    roll     : 0.0,
     scale    : Vector::new(1.0, 1.0),
   }
 }
@@ -74,6 +76,8 @@ fn rotate(degrees: f32) -> alphabet::Transform {
 fn scale(s: f32) -> alphabet::Transform {
   alphabet::Transform {
     rotation : 0.0,
+    pitch    : 0.0,
+    roll     : 0.0,
     scale    : Vector::new(s, s),
   }
 }
@@ -93,6 +97,8 @@ fn new() -> grammar::T<TextureId> {
       texture_id : TextureId::Stem,
       width      : 0.2,
       length     : 1.0,
+      taper      : 1.0,
+      bend       : 0.0,
     }
   };
 
@@ -107,7 +113,7 @@ fn new() -> grammar::T<TextureId> {
   let rules =
     rules
     .into_iter()
-    .map(|(actions, next)| grammar::RHS { actions: actions, next: next })
+    .map(|(actions, next)| vec!(grammar::RHS::new(actions.into_iter().map(|a| a.into()).collect(), next)))
     .collect();
 
   grammar::T {
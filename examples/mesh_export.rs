@@ -0,0 +1,63 @@
+/// Render a grammar loaded from a text file in 3D, and save it as an OBJ or glTF binary file (depending on the
+/// extension) to open in other tools, e.g.
+///   cargo run --example mesh_export examples/grammars/bent_tree.txt bent_tree.glb
+/// Pass `--flat` to export the 2D render instead.
+
+extern crate lsystems;
+extern crate rand;
+
+use std::io::Read;
+
+use lsystems::{gltf, mesh, obj, resources, text, word};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TextureId {
+  Stem,
+  Leaf,
+}
+
+impl std::str::FromStr for TextureId {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s {
+      "Stem" => Ok(TextureId::Stem),
+      "Leaf" => Ok(TextureId::Leaf),
+      _ => Err(()),
+    }
+  }
+}
+
+pub fn main() {
+  let usage = "usage: mesh_export <grammar file> <obj or glb file> [--flat]";
+  let path = std::env::args().nth(1).expect(usage);
+  let out = std::env::args().nth(2).expect(usage);
+  let flat = std::env::args().nth(3).map_or(false, |arg| arg == "--flat");
+  let mut contents = String::new();
+  std::fs::File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+
+  let t =
+    match text::parse::<TextureId>(&contents) {
+      Ok(t) => t,
+      Err(e) => panic!("{}: {}", path, e),
+    };
+
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([0x9abcdef0, 0x12345678, 0x98765432, 0x13371337]);
+  let resources = resources::NutrientsAndMass { available_nutrients: 1000000.0, max_mass: 1000000.0 };
+  let word = word::generate(&t, 1 << 6, 1 << 18, 0.01, &resources, &mut rng).unwrap();
+  let mesh =
+    if flat {
+      mesh::from_vertices(lsystems::render_indexed(&word))
+    } else {
+      lsystems::render_mesh(&word, &Default::default())
+    };
+
+  let name = |texture_id: &TextureId| format!("{:?}", texture_id);
+  let mut file = std::io::BufWriter::new(std::fs::File::create(&out).unwrap());
+  if out.ends_with(".obj") {
+    obj::write(&mesh.to_hashmap(), name, &mut file).unwrap();
+  } else if out.ends_with(".glb") {
+    gltf::write_glb(&mesh.to_hashmap(), name, &mut file).unwrap();
+  } else {
+    panic!("{}", usage);
+  }
+}
@@ -0,0 +1,47 @@
+/// Render a grammar loaded from a text file to a PNG image, without opening a window, e.g.
+///   cargo run --example png_export examples/grammars/fractal_plant.txt fractal_plant.png
+
+extern crate lsystems;
+extern crate rand;
+
+use std::io::Read;
+
+use lsystems::{raster, resources, text, word};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum TextureId {
+  Stem,
+}
+
+impl std::str::FromStr for TextureId {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s {
+      "Stem" => Ok(TextureId::Stem),
+      _ => Err(()),
+    }
+  }
+}
+
+pub fn main() {
+  let usage = "usage: png_export <grammar file> <png file>";
+  let path = std::env::args().nth(1).expect(usage);
+  let out = std::env::args().nth(2).expect(usage);
+  let mut contents = String::new();
+  std::fs::File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+
+  let t =
+    match text::parse::<TextureId>(&contents) {
+      Ok(t) => t,
+      Err(e) => panic!("{}: {}", path, e),
+    };
+
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([0x9abcdef0, 0x12345678, 0x98765432, 0x13371337]);
+  let resources = resources::NutrientsAndMass { available_nutrients: 1000000.0, max_mass: 1000000.0 };
+  let word = word::generate(&t, 1 << 6, 1 << 18, 0.01, &resources, &mut rng).unwrap();
+  let vertices = lsystems::render(&word).to_hashmap();
+
+  let mut image = raster::new(800, 800, [255, 255, 255, 255]);
+  image.draw(&vertices, |texture_id, _| match texture_id { &TextureId::Stem => [51, 128, 25, 255] });
+  image.write_png(std::fs::File::create(&out).unwrap()).unwrap();
+}
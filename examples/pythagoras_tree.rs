@@ -72,6 +72,8 @@ fn new() -> grammar::T<TextureId> {
       texture_id : TextureId::Wood,
       width      : 0.2,
       length      : 1.0,
+      taper      : 1.0,
+      bend       : 0.0,
     }
   };
 
@@ -79,6 +81,8 @@ fn new() -> grammar::T<TextureId> {
     alphabet::Terminal::Transform(
       alphabet::Transform {
         rotation : std::f32::consts::PI * degrees / 180.0,
+        pitch    : 0.0,
+        roll     : 0.0,
         scale    : Vector::new(1.0, 1.0),
       }
     )
@@ -88,6 +92,8 @@ fn new() -> grammar::T<TextureId> {
     alphabet::Terminal::Transform(
       alphabet::Transform {
         rotation : 0.0,
+        pitch    : 0.0,
+        roll     : 0.0,
         scale    : Vector::new(s, s),
       }
     )
@@ -102,7 +108,7 @@ fn new() -> grammar::T<TextureId> {
   let rules =
     rules
     .into_iter()
-    .map(|(actions, next)| grammar::RHS { actions: actions, next: next })
+    .map(|(actions, next)| vec!(grammar::RHS::new(actions.into_iter().map(|a| a.into()).collect(), next)))
     .collect();
 
   grammar::T {
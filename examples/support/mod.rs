@@ -6,6 +6,7 @@ use glium;
 use glutin;
 use lsystems;
 use lsystems::grammar;
+use lsystems::resources;
 use lsystems::word;
 use rand;
 use std;
@@ -53,14 +54,17 @@ pub fn main<Texture: self::Texture>(mut t: grammar::T<Texture>) {
     let mut target = window.draw();
     glium::Surface::clear(&mut target, None, Some((1.0, 1.0, 1.0, 1.0)), false, None, None);
 
-    let word = word::generate(&t, 1 << 6, 1 << 18, 0.01, 1000000.0, 1000000.0);
-    let vertices = lsystems::render(&word).to_hashmap();
+    // Reseed every frame, so stochastic grammars don't flicker.
+    let mut generate_rng: rand::XorShiftRng = rand::SeedableRng::from_seed([0x9abcdef0, 0x12345678, 0x98765432, 0x13371337]);
+    let resources = resources::NutrientsAndMass { available_nutrients: 1000000.0, max_mass: 1000000.0 };
+    let word = word::generate(&t, 1 << 6, 1 << 18, 0.01, &resources, &mut generate_rng).unwrap();
+    let vertices = lsystems::render_indexed(&word).to_indexed_hashmap();
 
     let mut min_x = std::f32::INFINITY;
     let mut min_y = std::f32::INFINITY;
     let mut max_x = std::f32::NEG_INFINITY;
     let mut max_y = std::f32::NEG_INFINITY;
-    for (_, vertices) in &vertices {
+    for (_, &(ref vertices, _)) in &vertices {
       for vertex in vertices {
         let x = vertex.screen_posn[0];
         let y = vertex.screen_posn[1];
@@ -71,8 +75,10 @@ pub fn main<Texture: self::Texture>(mut t: grammar::T<Texture>) {
       }
     }
 
-    for (texture_id, vertices) in &vertices {
-      let vertex_buffer = glium::VertexBuffer::new(&window, &vertices).unwrap();
+    for (texture_id, &(ref vertices, ref indices)) in &vertices {
+      let vertex_buffer = glium::VertexBuffer::new(&window, vertices).unwrap();
+      let index_buffer =
+        glium::IndexBuffer::new(&window, glium::index::PrimitiveType::TrianglesList, indices).unwrap();
 
       let program = shader_cache.get(&window, texture_id.clone());
 
@@ -87,7 +93,7 @@ pub fn main<Texture: self::Texture>(mut t: grammar::T<Texture>) {
       glium::Surface::draw(
         &mut target,
         &vertex_buffer,
-        glium::index::IndicesSource::NoIndices { primitives: glium::index::PrimitiveType::TrianglesList },
+        &index_buffer,
         program,
         &uniforms,
         &draw_parameters,
@@ -104,11 +110,11 @@ pub fn main<Texture: self::Texture>(mut t: grammar::T<Texture>) {
           match keycode {
             Down => {
               t = prev.clone();
-              lsystems::mutate(&mut t, &mut rng);
+              lsystems::mutate(&mut t, &mut rng).unwrap();
             },
             Up => {
               prev = t.clone();
-              lsystems::mutate(&mut t, &mut rng);
+              lsystems::mutate(&mut t, &mut rng).unwrap();
             },
             _ => {},
           }
@@ -0,0 +1,48 @@
+/// Render a grammar loaded from a text file to an SVG document on stdout, without opening a window, e.g.
+///   cargo run --example svg_export examples/grammars/fractal_plant.txt > fractal_plant.svg
+
+extern crate lsystems;
+extern crate rand;
+
+use std::io::Read;
+
+use lsystems::{resources, svg, text, word};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TextureId {
+  Stem,
+}
+
+impl std::str::FromStr for TextureId {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s {
+      "Stem" => Ok(TextureId::Stem),
+      _ => Err(()),
+    }
+  }
+}
+
+pub fn main() {
+  let path = std::env::args().nth(1).expect("usage: svg_export <grammar file>");
+  let mut contents = String::new();
+  std::fs::File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+
+  let t =
+    match text::parse::<TextureId>(&contents) {
+      Ok(t) => t,
+      Err(e) => panic!("{}: {}", path, e),
+    };
+
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([0x9abcdef0, 0x12345678, 0x98765432, 0x13371337]);
+  let resources = resources::NutrientsAndMass { available_nutrients: 1000000.0, max_mass: 1000000.0 };
+  let word = word::generate(&t, 1 << 6, 1 << 18, 0.01, &resources, &mut rng).unwrap();
+  let shapes = lsystems::render(&word).to_shapes();
+
+  let stdout = std::io::stdout();
+  svg::write(
+    &shapes,
+    |texture_id| match texture_id { &TextureId::Stem => "fill:#336619".to_string() },
+    &mut stdout.lock(),
+  ).unwrap();
+}
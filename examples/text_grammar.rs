@@ -0,0 +1,77 @@
+/// Render a grammar loaded from a text file, e.g.
+///   cargo run --example text_grammar examples/grammars/fractal_plant.txt
+
+extern crate cgmath;
+#[macro_use]
+extern crate glium;
+extern crate glutin;
+extern crate lsystems;
+extern crate rand;
+
+mod support;
+
+use std::io::Read;
+
+use lsystems::text;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TextureId {
+  Stem,
+}
+
+impl rand::Rand for TextureId {
+  fn rand<Rng: rand::Rng>(_: &mut Rng) -> Self {
+    TextureId::Stem
+  }
+}
+
+impl std::str::FromStr for TextureId {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s {
+      "Stem" => Ok(TextureId::Stem),
+      _ => Err(()),
+    }
+  }
+}
+
+impl std::fmt::Display for TextureId {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    std::fmt::Debug::fmt(self, f)
+  }
+}
+
+impl support::Texture for TextureId {
+  fn to_fragment_shader(&self) -> String {
+    match self {
+      &TextureId::Stem => "
+        #version 330
+
+        in vec2 f_texture_posn;
+
+        out vec4 frag_color;
+
+        void main() {
+          frag_color = vec4(0.2, 0.5, 0.1, 1);
+        }".to_string()
+    }
+  }
+}
+
+pub fn main() {
+  let path = std::env::args().nth(1).expect("usage: text_grammar <grammar file>");
+  let mut contents = String::new();
+  std::fs::File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+
+  let t =
+    match text::parse::<TextureId>(&contents) {
+      Ok(t) => t,
+      Err(e) => panic!("{}: {}", path, e),
+    };
+  if let Err(errors) = t.validate() {
+    for e in errors {
+      println!("{}: {}", path, e);
+    }
+  }
+  support::main(t)
+}
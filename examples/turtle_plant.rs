@@ -0,0 +1,64 @@
+/// https://en.wikipedia.org/wiki/L-system#Example_7:_Fractal_plant, imported from its textbook notation.
+
+extern crate cgmath;
+#[macro_use]
+extern crate glium;
+extern crate glutin;
+extern crate lsystems;
+extern crate rand;
+
+mod support;
+
+use lsystems::alphabet;
+use lsystems::turtle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TextureId {
+  Stem,
+}
+
+impl rand::Rand for TextureId {
+  fn rand<Rng: rand::Rng>(_: &mut Rng) -> Self {
+    TextureId::Stem
+  }
+}
+
+impl support::Texture for TextureId {
+  fn to_fragment_shader(&self) -> String {
+    match self {
+      &TextureId::Stem => "
+        #version 330
+
+        in vec2 f_texture_posn;
+
+        out vec4 frag_color;
+
+        void main() {
+          frag_color = vec4(0.2, 0.5, 0.1, 1);
+        }".to_string()
+    }
+  }
+}
+
+pub fn main() {
+  let branch =
+    alphabet::Terminal::AddBranch {
+      texture_id : TextureId::Stem,
+      width      : 0.2,
+      length     : 1.0,
+      taper      : 1.0,
+      bend       : 0.0,
+    };
+  let t =
+    turtle::to_grammar(
+      "X",
+      &[
+        ('X', "F+[[X]-X]-F[-FX]+X"),
+        ('F', "FF"),
+      ],
+      25.0,
+      &branch,
+      5,
+    ).unwrap();
+  support::main(t)
+}
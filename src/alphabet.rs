@@ -1,14 +1,34 @@
 use cgmath;
 
 use prelude::*;
+use expr::Expr;
 
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "cgmath::Vector2")]
+struct Vector2Def<S> {
+  x : S,
+  y : S,
+}
+
+/// `Value` is `f32` for concrete transforms, or `Expr` for ones computed from a nonterminal's parameters.
+/// In 3D, branches grow along the y axis, the plane is the xy plane, and z points up out of it.
 #[derive(Debug, Clone)]
-pub struct Transform {
-  pub rotation : f32,
-  pub scale    : Vector,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Transform<Value = f32> {
+  /// Counterclockwise, in radians. In 3D, this is the yaw, about the z axis.
+  pub rotation : Value,
+  /// Only used in 3D: the angle to tilt the y axis up towards the z axis.
+  pub pitch    : Value,
+  /// Only used in 3D: the angle to turn about the y axis.
+  pub roll     : Value,
+  /// In 3D, `scale.x` scales the z axis too, so branches stay round.
+  #[cfg_attr(feature = "serde", serde(with = "Vector2Def"))]
+  pub scale    : cgmath::Vector2<Value>,
 }
 
 impl Transform {
+  /// The 2D transform, which ignores `pitch` and `roll`.
   pub fn to_matrix(&self) -> Matrix {
     let mut scale: Matrix = cgmath::SquareMatrix::from_value(1.0);
     scale.x.x = self.scale.x;
@@ -23,15 +43,136 @@ impl Transform {
 
     rotate * scale
   }
+
+  /// The 3D transform: scale, then roll, then pitch, then yaw.
+  pub fn to_matrix3d(&self) -> Matrix4 {
+    let scale = Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.x);
+    Matrix4::from_angle_z(cgmath::Rad(self.rotation)) *
+    Matrix4::from_angle_x(cgmath::Rad(self.pitch)) *
+    Matrix4::from_angle_y(cgmath::Rad(self.roll)) *
+    scale
+  }
+}
+
+/// The transform `u` of the way along a branch (from 0 at its start to 1 at its end), relative to its start.
+/// The branch is a circular arc that turns through `bend` radians over its `length`.
+pub fn along_branch(length: f32, bend: f32, u: f32) -> Matrix {
+  let angle = bend * u;
+  let (x, y) =
+    if bend.abs() < 1e-6 {
+      (0.0, length * u)
+    } else {
+      let radius = length / bend;
+      (radius * (angle.cos() - 1.0), radius * angle.sin())
+    };
+  let mut transform = Transform { rotation: angle, pitch: 0.0, roll: 0.0, scale: Vector::new(1.0, 1.0) }.to_matrix();
+  transform.z.x = x;
+  transform.z.y = y;
+  transform
+}
+
+impl Transform<Expr> {
+  pub fn eval(&self, params: &[f32], depth: u32) -> Transform {
+    Transform {
+      rotation : self.rotation.eval(params, depth),
+      pitch    : self.pitch.eval(params, depth),
+      roll     : self.roll.eval(params, depth),
+      scale    : Vector::new(self.scale.x.eval(params, depth), self.scale.y.eval(params, depth)),
+    }
+  }
+}
+
+impl From<Transform> for Transform<Expr> {
+  fn from(t: Transform) -> Self {
+    Transform {
+      rotation : t.rotation.into(),
+      pitch    : t.pitch.into(),
+      roll     : t.roll.into(),
+      scale    : cgmath::Vector2::new(t.scale.x.into(), t.scale.y.into()),
+    }
+  }
 }
 
 /// The terminals in this alphabet. These represent actions with possible side effects!
+/// Words contain concrete terminals; grammars contain ones computed from their nonterminals' parameters.
 #[derive(Debug, Clone)]
-pub enum Terminal<Texture> {
-  Transform(Transform),
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Terminal<Texture, Value = f32> {
+  Transform(Transform<Value>),
   AddBranch {
     texture_id : Texture,
-    width      : f32,
-    length     : f32,
+    width      : Value,
+    length     : Value,
+    /// The width at the end of the branch, relative to `width`.
+    taper      : Value,
+    /// The angle the branch turns through (counterclockwise, in radians), bending it into an arc. The transform
+    /// ends up rotated by it.
+    bend       : Value,
+  },
+  /// A filled polygon (e.g. a leaf), given by its corners relative to the current transform. Doesn't move anything.
+  Polygon {
+    texture_id : Texture,
+    points     : Vec<[Value; 2]>,
   },
+  /// Move forward like `AddBranch`, without drawing anything.
+  Move {
+    length : Value,
+  },
+  /// Save the current transform.
+  Push,
+  /// Restore the most recently saved transform. Each rule's actions have their own stack, so this does nothing if
+  /// there's no matching `Push` earlier in the same rule.
+  Pop,
+}
+
+impl<Texture: Clone> Terminal<Texture, Expr> {
+  pub fn eval(&self, params: &[f32], depth: u32) -> Terminal<Texture> {
+    match self {
+      &Terminal::Transform(ref t) => Terminal::Transform(t.eval(params, depth)),
+      &Terminal::AddBranch { ref texture_id, ref width, ref length, ref taper, ref bend } => {
+        Terminal::AddBranch {
+          texture_id : texture_id.clone(),
+          width      : width.eval(params, depth),
+          length     : length.eval(params, depth),
+          taper      : taper.eval(params, depth),
+          bend       : bend.eval(params, depth),
+        }
+      },
+      &Terminal::Polygon { ref texture_id, ref points } => {
+        Terminal::Polygon {
+          texture_id : texture_id.clone(),
+          points     : points.iter().map(|p| [p[0].eval(params, depth), p[1].eval(params, depth)]).collect(),
+        }
+      },
+      &Terminal::Move { ref length } => Terminal::Move { length: length.eval(params, depth) },
+      &Terminal::Push => Terminal::Push,
+      &Terminal::Pop => Terminal::Pop,
+    }
+  }
+}
+
+impl<Texture> From<Terminal<Texture>> for Terminal<Texture, Expr> {
+  fn from(t: Terminal<Texture>) -> Self {
+    match t {
+      Terminal::Transform(t) => Terminal::Transform(t.into()),
+      Terminal::AddBranch { texture_id, width, length, taper, bend } => {
+        Terminal::AddBranch {
+          texture_id : texture_id,
+          width      : width.into(),
+          length     : length.into(),
+          taper      : taper.into(),
+          bend       : bend.into(),
+        }
+      },
+      Terminal::Polygon { texture_id, points } => {
+        Terminal::Polygon {
+          texture_id : texture_id,
+          points     : points.into_iter().map(|p| [p[0].into(), p[1].into()]).collect(),
+        }
+      },
+      Terminal::Move { length } => Terminal::Move { length: length.into() },
+      Terminal::Push => Terminal::Push,
+      Terminal::Pop => Terminal::Pop,
+    }
+  }
 }
@@ -0,0 +1,172 @@
+//! Arithmetic expressions and conditions over the parameters of a nonterminal.
+
+use std;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Expr {
+  Const(f32),
+  /// The nth parameter of the nonterminal being expanded.
+  Param(u32),
+  /// How many expansions deep the nonterminal being expanded is; the start symbol is at depth 0.
+  Depth,
+  Neg(Box<Expr>),
+  Add(Box<Expr>, Box<Expr>),
+  Sub(Box<Expr>, Box<Expr>),
+  Mul(Box<Expr>, Box<Expr>),
+  Div(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+  /// Evaluate this expression. Parameters that weren't passed are 0.
+  pub fn eval(&self, params: &[f32], depth: u32) -> f32 {
+    match self {
+      &Expr::Const(c)            => c,
+      &Expr::Param(i)            => params.get(i as usize).cloned().unwrap_or(0.0),
+      &Expr::Depth               => depth as f32,
+      &Expr::Neg(ref e)          => -e.eval(params, depth),
+      &Expr::Add(ref l, ref r)   => l.eval(params, depth) + r.eval(params, depth),
+      &Expr::Sub(ref l, ref r)   => l.eval(params, depth) - r.eval(params, depth),
+      &Expr::Mul(ref l, ref r)   => l.eval(params, depth) * r.eval(params, depth),
+      &Expr::Div(ref l, ref r)   => l.eval(params, depth) / r.eval(params, depth),
+    }
+  }
+
+  pub fn as_const(&self) -> Option<f32> {
+    match self {
+      &Expr::Const(c) => Some(c),
+      _ => None,
+    }
+  }
+
+  /// One more than the highest parameter index this expression uses.
+  pub fn arity(&self) -> u32 {
+    match self {
+      &Expr::Const(_)            => 0,
+      &Expr::Param(i)            => i + 1,
+      &Expr::Depth               => 0,
+      &Expr::Neg(ref e)          => e.arity(),
+      &Expr::Add(ref l, ref r) |
+      &Expr::Sub(ref l, ref r) |
+      &Expr::Mul(ref l, ref r) |
+      &Expr::Div(ref l, ref r)   => std::cmp::max(l.arity(), r.arity()),
+    }
+  }
+
+  pub fn uses_depth(&self) -> bool {
+    match self {
+      &Expr::Const(_)            => false,
+      &Expr::Param(_)            => false,
+      &Expr::Depth               => true,
+      &Expr::Neg(ref e)          => e.uses_depth(),
+      &Expr::Add(ref l, ref r) |
+      &Expr::Sub(ref l, ref r) |
+      &Expr::Mul(ref l, ref r) |
+      &Expr::Div(ref l, ref r)   => l.uses_depth() || r.uses_depth(),
+    }
+  }
+
+  /// Replace each parameter with the corresponding expression from `params`, like `eval` does with values.
+  pub fn substitute(&self, params: &[Expr]) -> Expr {
+    let sub = |e: &Expr| Box::new(e.substitute(params));
+    match self {
+      &Expr::Const(c)            => Expr::Const(c),
+      &Expr::Param(i)            => params.get(i as usize).cloned().unwrap_or(Expr::Const(0.0)),
+      &Expr::Depth               => Expr::Depth,
+      &Expr::Neg(ref e)          => Expr::Neg(sub(e)),
+      &Expr::Add(ref l, ref r)   => Expr::Add(sub(l), sub(r)),
+      &Expr::Sub(ref l, ref r)   => Expr::Sub(sub(l), sub(r)),
+      &Expr::Mul(ref l, ref r)   => Expr::Mul(sub(l), sub(r)),
+      &Expr::Div(ref l, ref r)   => Expr::Div(sub(l), sub(r)),
+    }
+  }
+}
+
+impl From<f32> for Expr {
+  fn from(c: f32) -> Self {
+    Expr::Const(c)
+  }
+}
+
+impl std::ops::Add<f32> for Expr {
+  type Output = Expr;
+  fn add(self, rhs: f32) -> Expr {
+    match self {
+      Expr::Const(c) => Expr::Const(c + rhs),
+      Expr::Add(e, c) => {
+        match *c {
+          Expr::Const(c) => Expr::Add(e, Box::new(Expr::Const(c + rhs))),
+          c => Expr::Add(Box::new(Expr::Add(e, Box::new(c))), Box::new(Expr::Const(rhs))),
+        }
+      },
+      e => Expr::Add(Box::new(e), Box::new(Expr::Const(rhs))),
+    }
+  }
+}
+
+impl std::ops::Mul<f32> for Expr {
+  type Output = Expr;
+  fn mul(self, rhs: f32) -> Expr {
+    match self {
+      Expr::Const(c) => Expr::Const(c * rhs),
+      Expr::Mul(e, c) => {
+        match *c {
+          Expr::Const(c) => Expr::Mul(e, Box::new(Expr::Const(c * rhs))),
+          c => Expr::Mul(Box::new(Expr::Mul(e, Box::new(c))), Box::new(Expr::Const(rhs))),
+        }
+      },
+      e => Expr::Mul(Box::new(e), Box::new(Expr::Const(rhs))),
+    }
+  }
+}
+
+impl std::ops::AddAssign<f32> for Expr {
+  fn add_assign(&mut self, rhs: f32) {
+    *self = std::mem::replace(self, Expr::Const(0.0)) + rhs;
+  }
+}
+
+impl std::ops::MulAssign<f32> for Expr {
+  fn mul_assign(&mut self, rhs: f32) {
+    *self = std::mem::replace(self, Expr::Const(0.0)) * rhs;
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Comparison {
+  Less,
+  LessEqual,
+  Greater,
+  GreaterEqual,
+  Equal,
+  NotEqual,
+}
+
+/// A comparison between two expressions, used to guard which rules apply.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Condition {
+  pub lhs        : Expr,
+  pub comparison : Comparison,
+  pub rhs        : Expr,
+}
+
+impl Condition {
+  pub fn holds(&self, params: &[f32], depth: u32) -> bool {
+    let lhs = self.lhs.eval(params, depth);
+    let rhs = self.rhs.eval(params, depth);
+    match self.comparison {
+      Comparison::Less         => lhs < rhs,
+      Comparison::LessEqual    => lhs <= rhs,
+      Comparison::Greater      => lhs > rhs,
+      Comparison::GreaterEqual => lhs >= rhs,
+      Comparison::Equal        => lhs == rhs,
+      Comparison::NotEqual     => lhs != rhs,
+    }
+  }
+
+  pub fn arity(&self) -> u32 {
+    std::cmp::max(self.lhs.arity(), self.rhs.arity())
+  }
+}
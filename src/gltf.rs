@@ -0,0 +1,157 @@
+//! Export meshes as binary glTF 2.0 (`.glb`) files, e.g. to open plants in Blender or load them into engines.
+
+use std;
+
+use mesh;
+
+// Constants from the glTF 2.0 spec.
+const MAGIC: u32 = 0x46546C67;
+const JSON_CHUNK: u32 = 0x4E4F534A;
+const BIN_CHUNK: u32 = 0x004E4942;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Bytes per interleaved vertex: position, normal and texture position.
+const VERTEX_SIZE: usize = 4 * (3 + 3 + 2);
+
+fn escape(s: &str) -> String {
+  let mut escaped = String::new();
+  for c in s.chars() {
+    match c {
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+fn push_f32(buffer: &mut Vec<u8>, x: f32) {
+  push_u32(buffer, x.to_bits());
+}
+
+fn push_u32(buffer: &mut Vec<u8>, x: u32) {
+  buffer.extend_from_slice(&[x as u8, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8]);
+}
+
+/// Write `mesh` (see `mesh::T::to_hashmap`) as a glTF binary with one node and mesh, which has a primitive for
+/// each texture id, using a material named `name(texture_id)`. Primitives are written in order of their names.
+/// Texture positions are mapped from -1 to 1 to glTF's 0 to 1, with v pointing down.
+pub fn write_glb<TextureId, Name, W>(
+  mesh     : &std::collections::HashMap<TextureId, (Vec<mesh::Vertex>, Vec<u32>)>,
+  mut name : Name,
+  w        : &mut W,
+) -> std::io::Result<()> where
+  TextureId : Eq + std::hash::Hash,
+  Name      : FnMut(&TextureId) -> String,
+  W         : std::io::Write,
+{
+  let mut groups: Vec<(String, &Vec<mesh::Vertex>, &Vec<u32>)> =
+    mesh.iter()
+    .filter(|&(_, &(_, ref indices))| !indices.is_empty())
+    .map(|(texture_id, &(ref vertices, ref indices))| (name(texture_id), vertices, indices))
+    .collect();
+  groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+  // Each primitive has a buffer view of interleaved vertices, and one of indices.
+  let mut bin = vec!();
+  let mut materials = vec!();
+  let mut primitives = vec!();
+  let mut buffer_views = vec!();
+  let mut accessors = vec!();
+  for (i, (name, vertices, indices)) in groups.into_iter().enumerate() {
+    let mut min = [std::f32::INFINITY; 3];
+    let mut max = [std::f32::NEG_INFINITY; 3];
+    let vertices_offset = bin.len();
+    for v in vertices {
+      for j in 0 .. 3 {
+        min[j] = min[j].min(v.position[j]);
+        max[j] = max[j].max(v.position[j]);
+        push_f32(&mut bin, v.position[j]);
+      }
+      for j in 0 .. 3 {
+        push_f32(&mut bin, v.normal[j]);
+      }
+      push_f32(&mut bin, (v.texture_posn[0] + 1.0) / 2.0);
+      push_f32(&mut bin, (1.0 - v.texture_posn[1]) / 2.0);
+    }
+    let indices_offset = bin.len();
+    for &index in indices {
+      push_u32(&mut bin, index);
+    }
+
+    buffer_views.push(format!(
+      "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"byteStride\":{},\"target\":{}}}",
+      vertices_offset, indices_offset - vertices_offset, VERTEX_SIZE, ARRAY_BUFFER,
+    ));
+    buffer_views.push(format!(
+      "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":{}}}",
+      indices_offset, bin.len() - indices_offset, ELEMENT_ARRAY_BUFFER,
+    ));
+
+    let view = 2 * i;
+    let accessor = 4 * i;
+    accessors.push(format!(
+      "{{\"bufferView\":{},\"byteOffset\":0,\"componentType\":{},\"count\":{},\"type\":\"VEC3\",\
+       \"min\":[{},{},{}],\"max\":[{},{},{}]}}",
+      view, FLOAT, vertices.len(), min[0], min[1], min[2], max[0], max[1], max[2],
+    ));
+    accessors.push(format!(
+      "{{\"bufferView\":{},\"byteOffset\":12,\"componentType\":{},\"count\":{},\"type\":\"VEC3\"}}",
+      view, FLOAT, vertices.len(),
+    ));
+    accessors.push(format!(
+      "{{\"bufferView\":{},\"byteOffset\":24,\"componentType\":{},\"count\":{},\"type\":\"VEC2\"}}",
+      view, FLOAT, vertices.len(),
+    ));
+    accessors.push(format!(
+      "{{\"bufferView\":{},\"componentType\":{},\"count\":{},\"type\":\"SCALAR\"}}",
+      view + 1, UNSIGNED_INT, indices.len(),
+    ));
+
+    primitives.push(format!(
+      "{{\"attributes\":{{\"POSITION\":{},\"NORMAL\":{},\"TEXCOORD_0\":{}}},\"indices\":{},\"material\":{}}}",
+      accessor, accessor + 1, accessor + 2, accessor + 3, i,
+    ));
+    materials.push(format!("{{\"name\":\"{}\",\"doubleSided\":true}}", escape(&name)));
+  }
+
+  let mut json = String::from("{\"asset\":{\"version\":\"2.0\",\"generator\":\"lsystems\"},\"scene\":0,");
+  if primitives.is_empty() {
+    json.push_str("\"scenes\":[{\"nodes\":[0]}],\"nodes\":[{}]}");
+  } else {
+    json.push_str(&format!(
+      "\"scenes\":[{{\"nodes\":[0]}}],\"nodes\":[{{\"mesh\":0}}],\"meshes\":[{{\"primitives\":[{}]}}],\
+       \"materials\":[{}],\"buffers\":[{{\"byteLength\":{}}}],\"bufferViews\":[{}],\"accessors\":[{}]}}",
+      primitives.join(","), materials.join(","), bin.len(), buffer_views.join(","), accessors.join(","),
+    ));
+  }
+
+  // Chunks are padded to multiples of 4 bytes: JSON with spaces, and binary data with zeros.
+  let mut json = json.into_bytes();
+  while json.len() % 4 != 0 {
+    json.push(b' ');
+  }
+  let has_bin = !bin.is_empty();
+
+  let mut header = vec!();
+  let length = 12 + 8 + json.len() + if has_bin { 8 + bin.len() } else { 0 };
+  push_u32(&mut header, MAGIC);
+  push_u32(&mut header, 2);
+  push_u32(&mut header, length as u32);
+  push_u32(&mut header, json.len() as u32);
+  push_u32(&mut header, JSON_CHUNK);
+  w.write_all(&header)?;
+  w.write_all(&json)?;
+  if has_bin {
+    let mut header = vec!();
+    push_u32(&mut header, bin.len() as u32);
+    push_u32(&mut header, BIN_CHUNK);
+    w.write_all(&header)?;
+    w.write_all(&bin)?;
+  }
+  Ok(())
+}
@@ -1,20 +1,236 @@
 //! This module implements languages/grammars/words with rules of the form
-///   Nonterminal -> Terminal Nonterminal*
-/// Every Nonterminal should appear on the left hand side of no more than one rule.
+//!   Nonterminal(params) -> Terminal Nonterminal(args)*
+//! A Nonterminal can have several weighted alternatives for its right hand side; every time the Nonterminal is
+//! expanded, one of the alternatives whose guard and context hold is picked at random. Alternatives with more
+//! specific contexts take precedence over ones with less specific contexts.
+
+use std;
 
 use alphabet::*;
+use expr::{Condition, Expr};
+
+pub use simplify::{simplify, simplify_with, SimplifyOptions};
 
 /// A Nonterminal is just a rule index.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Nonterminal(pub u32);
 
+/// Where a Nonterminal appears in a word: the Nonterminal it was expanded from, and its neighbours in that
+/// Nonterminal's right hand side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Context {
+  pub parent : Option<Nonterminal>,
+  pub left   : Option<Nonterminal>,
+  pub right  : Option<Nonterminal>,
+}
+
+impl Context {
+  /// Treating this context as a pattern, check whether it matches `actual`. `None`s in the pattern match anything.
+  pub fn matches(&self, actual: &Context) -> bool {
+    let matches = |pattern: Option<Nonterminal>, actual: Option<Nonterminal>| pattern.map_or(true, |_| pattern == actual);
+    matches(self.parent, actual.parent) && matches(self.left, actual.left) && matches(self.right, actual.right)
+  }
+
+  /// How many parts of the context this pattern constrains.
+  pub fn specificity(&self) -> u32 {
+    self.parent.is_some() as u32 + self.left.is_some() as u32 + self.right.is_some() as u32
+  }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RHS<Texture> {
-  pub actions : Vec<Terminal<Texture>>,
+  /// The relative likelihood of picking this alternative.
+  pub weight  : f32,
+  /// This alternative is only picked if all of these hold.
+  pub guard   : Vec<Condition>,
+  /// This alternative is only picked if the Nonterminal appears in a matching context.
+  pub context : Context,
+  /// These can refer to the parameters of the Nonterminal being expanded.
+  pub actions : Vec<Terminal<Texture, Expr>>,
   pub next    : Vec<Nonterminal>,
+  /// The arguments passed to each of `next`. Missing arguments are 0.
+  pub args    : Vec<Vec<Expr>>,
+}
+
+impl<Texture> RHS<Texture> {
+  pub fn new(actions: Vec<Terminal<Texture, Expr>>, next: Vec<Nonterminal>) -> Self {
+    RHS {
+      weight  : 1.0,
+      guard   : vec!(),
+      context : Default::default(),
+      actions : actions,
+      next    : next,
+      args    : vec!(),
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct T<Texture> {
-  pub rules: Vec<RHS<Texture>>,
+  /// The alternatives for each Nonterminal, indexed by Nonterminal.
+  pub rules: Vec<Vec<RHS<Texture>>>,
+}
+
+/// Problems with a grammar. Alternatives are identified by their Nonterminal and index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+  /// There are no rules, so there's no start symbol.
+  Empty,
+  /// A Nonterminal is referred to that has no entry in `rules`.
+  UndefinedNonterminal { rule: Nonterminal, alternative: usize, nonterminal: Nonterminal },
+  /// A Nonterminal that can never be reached from the start symbol.
+  Unreachable(Nonterminal),
+  InvalidWeight { rule: Nonterminal, alternative: usize },
+  /// A transform, move or branch bend that isn't finite.
+  NonFiniteTransform { rule: Nonterminal, alternative: usize, action: usize },
+  /// A branch with a negative width, length or taper.
+  NegativeBranch { rule: Nonterminal, alternative: usize, action: usize },
+  /// A polygon with fewer than three points, or non-finite ones.
+  InvalidPolygon { rule: Nonterminal, alternative: usize, action: usize },
+  /// A `Pop` without a matching `Push` earlier in the same alternative.
+  UnmatchedPop { rule: Nonterminal, alternative: usize, action: usize },
+}
+
+impl Error {
+  /// Whether this error makes the grammar unusable, rather than just suspicious.
+  pub fn is_fatal(&self) -> bool {
+    match self {
+      &Error::Empty | &Error::UndefinedNonterminal { .. } => true,
+      _ => false,
+    }
+  }
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      &Error::Empty =>
+        write!(f, "grammar has no rules"),
+      &Error::UndefinedNonterminal { rule, alternative, nonterminal } =>
+        write!(f, "rule {}.{} refers to undefined nonterminal {}", rule.0, alternative, nonterminal.0),
+      &Error::Unreachable(rule) =>
+        write!(f, "rule {} is unreachable", rule.0),
+      &Error::InvalidWeight { rule, alternative } =>
+        write!(f, "rule {}.{} has a negative or non-finite weight", rule.0, alternative),
+      &Error::NonFiniteTransform { rule, alternative, action } =>
+        write!(f, "action {} of rule {}.{} is a non-finite transform", action, rule.0, alternative),
+      &Error::NegativeBranch { rule, alternative, action } =>
+        write!(f, "action {} of rule {}.{} is a branch with negative width, length or taper", action, rule.0, alternative),
+      &Error::InvalidPolygon { rule, alternative, action } =>
+        write!(f, "action {} of rule {}.{} is a polygon with too few or non-finite points", action, rule.0, alternative),
+      &Error::UnmatchedPop { rule, alternative, action } =>
+        write!(f, "action {} of rule {}.{} pops without a matching push", action, rule.0, alternative),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl<Texture> T<Texture> {
+  /// Check this grammar for problems. Only constant values are checked, since parametric ones aren't known until
+  /// the grammar is expanded.
+  pub fn validate(&self) -> Result<(), Vec<Error>> {
+    if self.rules.is_empty() {
+      return Err(vec!(Error::Empty))
+    }
+
+    let mut errors = vec!();
+    let is_finite = |e: &Expr| e.as_const().map_or(true, |c| c.is_finite());
+    let is_negative = |e: &Expr| e.as_const().map_or(false, |c| c < 0.0);
+
+    for (i, alternatives) in self.rules.iter().enumerate() {
+      let rule = Nonterminal(i as u32);
+      for (alternative, rhs) in alternatives.iter().enumerate() {
+        if !(rhs.weight.is_finite() && rhs.weight >= 0.0) {
+          errors.push(Error::InvalidWeight { rule: rule, alternative: alternative });
+        }
+
+        let mut pushes = 0;
+        for (action, terminal) in rhs.actions.iter().enumerate() {
+          match terminal {
+            &Terminal::Transform(ref t) => {
+              if !([&t.rotation, &t.pitch, &t.roll, &t.scale.x, &t.scale.y].iter().all(|e| is_finite(e))) {
+                errors.push(Error::NonFiniteTransform { rule: rule, alternative: alternative, action: action });
+              }
+            },
+            &Terminal::AddBranch { ref width, ref length, ref taper, ref bend, .. } => {
+              if is_negative(width) || is_negative(length) || is_negative(taper) {
+                errors.push(Error::NegativeBranch { rule: rule, alternative: alternative, action: action });
+              }
+              if !is_finite(bend) {
+                errors.push(Error::NonFiniteTransform { rule: rule, alternative: alternative, action: action });
+              }
+            },
+            &Terminal::Polygon { ref points, .. } => {
+              if points.len() < 3 || !points.iter().all(|p| is_finite(&p[0]) && is_finite(&p[1])) {
+                errors.push(Error::InvalidPolygon { rule: rule, alternative: alternative, action: action });
+              }
+            },
+            &Terminal::Move { ref length } => {
+              if !is_finite(length) {
+                errors.push(Error::NonFiniteTransform { rule: rule, alternative: alternative, action: action });
+              }
+            },
+            &Terminal::Push => {
+              pushes += 1;
+            },
+            &Terminal::Pop => {
+              if pushes == 0 {
+                errors.push(Error::UnmatchedPop { rule: rule, alternative: alternative, action: action });
+              } else {
+                pushes -= 1;
+              }
+            },
+          }
+        }
+
+        let context = rhs.context.parent.iter().chain(rhs.context.left.iter()).chain(rhs.context.right.iter());
+        for &nt in rhs.next.iter().chain(context) {
+          if nt.0 as usize >= self.rules.len() {
+            errors.push(Error::UndefinedNonterminal { rule: rule, alternative: alternative, nonterminal: nt });
+          }
+        }
+      }
+    }
+
+    let mut reachable = vec!(false; self.rules.len());
+    let mut pending = vec!(Nonterminal(0));
+    reachable[0] = true;
+    while let Some(nt) = pending.pop() {
+      for rhs in &self.rules[nt.0 as usize] {
+        for &next in &rhs.next {
+          if (next.0 as usize) < self.rules.len() && !reachable[next.0 as usize] {
+            reachable[next.0 as usize] = true;
+            pending.push(next);
+          }
+        }
+      }
+    }
+    for (i, &reachable) in reachable.iter().enumerate() {
+      if !reachable {
+        errors.push(Error::Unreachable(Nonterminal(i as u32)));
+      }
+    }
+
+    if errors.is_empty() {
+      Ok(())
+    } else {
+      Err(errors)
+    }
+  }
+
+  /// Like `validate`, but only reports the errors that make the grammar unusable.
+  pub fn check(&self) -> Result<(), Vec<Error>> {
+    match self.validate() {
+      Ok(()) => Ok(()),
+      Err(errors) => {
+        let errors: Vec<Error> = errors.into_iter().filter(|e| e.is_fatal()).collect();
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+      },
+    }
+  }
 }
@@ -0,0 +1,74 @@
+//! 3D triangle meshes, as made by `render_mesh` (or from 2D renders by `from_vertices`).
+
+use std;
+
+use vertices;
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Vertex {
+  pub position     : [f32; 3],
+  /// Unit length, pointing out of the surface.
+  pub normal       : [f32; 3],
+  pub texture_posn : [f32; 2],
+}
+
+implement_vertex!(Vertex, position, normal, texture_posn);
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+  feature = "serde",
+  serde(bound(deserialize = "TextureId: Eq + std::hash::Hash + serde::Deserialize<'de>")),
+)]
+pub struct T<TextureId> {
+  // vertices and counterclockwise triangles (as indices into them) by texture id
+  triangles: std::collections::HashMap<TextureId, (Vec<Vertex>, Vec<u32>)>,
+}
+
+pub fn new<TextureId: Eq + std::hash::Hash>() -> T<TextureId> {
+  T {
+    triangles: std::collections::HashMap::new(),
+  }
+}
+
+/// A 2D render as a flat mesh in the xy plane, facing +z.
+pub fn from_vertices<TextureId: Eq + std::hash::Hash>(vertices: vertices::T<TextureId>) -> T<TextureId> {
+  let mut t = new();
+  for (texture_id, (vertices, mut indices)) in vertices.to_indexed_hashmap() {
+    // 2D triangles can go either way around.
+    for triangle in indices.chunks_mut(3).filter(|triangle| triangle.len() == 3) {
+      let p: Vec<[f32; 2]> = triangle.iter().map(|&i| vertices[i as usize].screen_posn).collect();
+      if (p[1][0] - p[0][0]) * (p[2][1] - p[0][1]) - (p[1][1] - p[0][1]) * (p[2][0] - p[0][0]) < 0.0 {
+        triangle.swap(1, 2);
+      }
+    }
+    let vertices: Vec<Vertex> =
+      vertices.iter()
+      .map(|v| {
+        Vertex {
+          position     : [v.screen_posn[0], v.screen_posn[1], 0.0],
+          normal       : [0.0, 0.0, 1.0],
+          texture_posn : v.texture_posn,
+        }
+      })
+      .collect();
+    t.push_indexed(texture_id, &vertices, &indices);
+  }
+  t
+}
+
+impl<TextureId: Eq + std::hash::Hash> T<TextureId> {
+  /// Add triangles given as indices into `vertices`.
+  pub fn push_indexed(&mut self, texture_id: TextureId, vertices: &[Vertex], indices: &[u32]) {
+    let &mut (ref mut triangle_vertices, ref mut triangle_indices) =
+      self.triangles.entry(texture_id).or_insert_with(|| (vec!(), vec!()));
+    let offset = triangle_vertices.len() as u32;
+    triangle_indices.extend(indices.iter().map(|&i| offset + i));
+    triangle_vertices.extend_from_slice(vertices);
+  }
+
+  /// The triangles for each texture id, as vertices and indices into them.
+  pub fn to_hashmap(self) -> std::collections::HashMap<TextureId, (Vec<Vertex>, Vec<u32>)> {
+    self.triangles
+  }
+}
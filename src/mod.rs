@@ -5,18 +5,32 @@ extern crate cgmath;
 extern crate glium;
 #[macro_use]
 extern crate log;
+extern crate png;
 extern crate rand;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 extern crate time;
 
 mod mutate;
 mod prelude;
 mod render;
+mod simplify;
 
 pub mod alphabet;
+pub mod expr;
+pub mod gltf;
 pub mod grammar;
+pub mod mesh;
+pub mod obj;
+pub mod raster;
+pub mod resources;
+pub mod svg;
+pub mod text;
+pub mod turtle;
 pub mod vertex;
 pub mod vertices;
 pub mod word;
 
 pub use mutate::mutate;
-pub use render::render;
+pub use render::{render, render_indexed, render_mesh, render_with, JointStyle, RenderOptions};
@@ -2,15 +2,20 @@ use rand;
 use std;
 
 use alphabet::*;
+use expr::Expr;
 use prelude::*;
 use grammar::*;
 
 const MAX_ANGLE_CHANGE: f32 = std::f32::consts::PI / 12.0;
 const MAX_SCALE_CHANGE: f32 = 0.1;
 
+fn alternatives<Texture>(t: &T<Texture>) -> usize {
+  t.rules.iter().map(|alternatives| alternatives.len()).sum()
+}
+
 fn len<Texture>(t: &T<Texture>) -> usize {
   let mut len = 0;
-  for rhs in &t.rules {
+  for rhs in t.rules.iter().flat_map(|alternatives| alternatives.iter()) {
     len += rhs.actions.len();
     len += rhs.next.len();
   }
@@ -25,7 +30,7 @@ fn random_rerotate<Rng: rand::Rng>(rng: &mut Rng) -> f32 {
   rng.next_f32() * MAX_ANGLE_CHANGE*2.0 - MAX_ANGLE_CHANGE
 }
 
-fn point_action<Texture, Rng: rand::Rng>(t: &mut Terminal<Texture>, rng: &mut Rng) {
+fn point_action<Texture, Rng: rand::Rng>(t: &mut Terminal<Texture, Expr>, rng: &mut Rng) {
   match t {
     &mut Terminal::Transform(ref mut t) => {
       let mut f = rng.next_f32();
@@ -33,6 +38,10 @@ fn point_action<Texture, Rng: rand::Rng>(t: &mut Terminal<Texture>, rng: &mut Rn
       f -= 0.5;
       if f < 0.0 {
         t.rotation *= random_rerotate(rng);
+        // Scaling no rotation could make it -0, which is the same thing but doesn't print the same.
+        if t.rotation == Expr::Const(0.0) {
+          t.rotation = Expr::Const(0.0);
+        }
         return
       }
 
@@ -42,23 +51,64 @@ fn point_action<Texture, Rng: rand::Rng>(t: &mut Terminal<Texture>, rng: &mut Rn
         t.scale.y *= random_rescale(rng);
         return
       }
+
+      // Only transforms that are already 3D get tilted, so that flat grammars stay flat.
+      if t.pitch != Expr::Const(0.0) || t.roll != Expr::Const(0.0) {
+        if rng.gen() {
+          t.pitch += random_rerotate(rng);
+        } else {
+          t.roll += random_rerotate(rng);
+        }
+      }
     },
-    &mut Terminal::AddBranch { ref mut width, ref mut length, .. } => {
-      if rng.next_f32() < 0.5 {
+    &mut Terminal::AddBranch { ref mut width, ref mut length, ref mut taper, ref mut bend, .. } => {
+      let mut f = rng.next_f32();
+
+      f -= 0.35;
+      if f < 0.0 {
         *width += rng.next_f32();
         return
       }
 
+      f -= 0.35;
+      if f < 0.0 {
+        *length += rng.next_f32();
+        return
+      }
+
+      f -= 0.15;
+      if f < 0.0 {
+        *taper *= random_rescale(rng);
+        return
+      }
+
+      *bend += random_rerotate(rng);
+    },
+    &mut Terminal::Polygon { ref mut points, .. } => {
+      if points.is_empty() {
+        return
+      }
+      let i = rng.gen_range(0, points.len());
+      points[i][0] *= random_rescale(rng);
+      points[i][1] *= random_rescale(rng);
+    },
+    &mut Terminal::Move { ref mut length } => {
       *length += rng.next_f32();
     },
+    &mut Terminal::Push | &mut Terminal::Pop => {},
   }
 }
 
 fn random_nonterminal<Texture, Rng: rand::Rng>(t: &T<Texture>, rng: &mut Rng) -> Nonterminal {
   let max_idx = len(t);
+  if max_idx == 0 {
+    // There are no symbols to weight the rules by.
+    return Nonterminal(rng.gen_range(0, t.rules.len() as u32))
+  }
+
   let mut idx = rng.gen_range(0, max_idx);
-  for (i, rhs) in t.rules.iter().enumerate() {
-    let rule_len = rhs.actions.len() + rhs.next.len();
+  for (i, alternatives) in t.rules.iter().enumerate() {
+    let rule_len: usize = alternatives.iter().map(|rhs| rhs.actions.len() + rhs.next.len()).sum();
     if idx < rule_len {
       return Nonterminal(i as u32)
     }
@@ -70,10 +120,14 @@ fn random_nonterminal<Texture, Rng: rand::Rng>(t: &T<Texture>, rng: &mut Rng) ->
 
 fn point<Texture, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) {
   let max_idx = len(t);
+  if max_idx == 0 {
+    return
+  }
+
   let mut idx = rng.gen_range(0, max_idx);
   let t_ptr = t as *const T<Texture>;
 
-  for rhs in &mut t.rules {
+  for rhs in t.rules.iter_mut().flat_map(|alternatives| alternatives.iter_mut()) {
     if idx < rhs.actions.len() {
       point_action(&mut rhs.actions[idx], rng);
       return
@@ -92,40 +146,55 @@ fn point<Texture, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) {
 fn random_action<Texture: rand::Rand, Rng: rand::Rng>(rng: &mut Rng) -> Terminal<Texture> {
   let mut f = rng.next_f32();
 
-  f -= 0.5;
+  f -= 0.4;
   if f < 0.0 {
     return
       Terminal::AddBranch {
         texture_id : rand::Rand::rand(rng),
         width      : rng.next_f32() * 0.2 + 0.1,
         length     : rng.next_f32(),
+        taper      : 1.0,
+        bend       : 0.0,
       }
   }
 
+  f -= 0.1;
+  if f < 0.0 {
+    return Terminal::Move { length: rng.next_f32() }
+  }
+
   f -= 0.25;
   if f < 0.0 {
     return
       Terminal::Transform(Transform {
         rotation : random_rerotate(rng),
+        pitch    : 0.0,
+        roll     : 0.0,
         scale    : Vector::new(1.0, 1.0)
       })
   }
 
   Terminal::Transform(Transform {
     rotation : 0.0,
+    pitch    : 0.0,
+    roll     : 0.0,
     scale    : Vector::new(random_rescale(rng), random_rescale(rng)),
   })
 }
 
 fn add<Texture: rand::Rand, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) {
   // We can insert before any symbol, or at the end of any chromosome.
-  let max_idx = len(t) + t.rules.len();
+  let max_idx = len(t) + alternatives(t);
+  if max_idx == 0 {
+    return
+  }
+
   let mut idx = rng.gen_range(0, max_idx);
   let t_ptr = t as *const T<Texture>;
 
-  for rhs in &mut t.rules {
+  for rhs in t.rules.iter_mut().flat_map(|alternatives| alternatives.iter_mut()) {
     if idx < rhs.actions.len() + 1 {
-      rhs.actions.insert(idx, random_action(rng));
+      rhs.actions.insert(idx, random_action(rng).into());
       return
     }
     idx -= rhs.actions.len() + 1;
@@ -133,6 +202,10 @@ fn add<Texture: rand::Rand, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) {
     if idx < rhs.next.len() + 1 {
       let t: &T<Texture> = unsafe { &*t_ptr };
       rhs.next.insert(idx, random_nonterminal(t, rng));
+      // Rules that don't pass any arguments leave `args` empty.
+      if !rhs.args.is_empty() && idx <= rhs.args.len() {
+        rhs.args.insert(idx, vec!());
+      }
       return
     }
     idx -= rhs.next.len() + 1;
@@ -141,9 +214,13 @@ fn add<Texture: rand::Rand, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) {
 
 fn remove<Texture, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) {
   let max_idx = len(t);
+  if max_idx == 0 {
+    return
+  }
+
   let mut idx = rng.gen_range(0, max_idx);
 
-  for rhs in &mut t.rules {
+  for rhs in t.rules.iter_mut().flat_map(|alternatives| alternatives.iter_mut()) {
     if idx < rhs.actions.len() {
       rhs.actions.remove(idx);
       return
@@ -152,32 +229,42 @@ fn remove<Texture, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) {
 
     if idx < rhs.next.len() {
       rhs.next.remove(idx);
+      if idx < rhs.args.len() {
+        rhs.args.remove(idx);
+      }
+      if rhs.args.iter().all(|args| args.is_empty()) {
+        rhs.args.clear();
+      }
       return
     }
     idx -= rhs.next.len();
   }
 }
 
-pub fn mutate<Texture: rand::Rand, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) {
+/// Randomly mutate `t`. Fails, leaving `t` alone, if the grammar has any fatal errors (see `grammar::T::check`).
+pub fn mutate<Texture: rand::Rand, Rng: rand::Rng>(t: &mut T<Texture>, rng: &mut Rng) -> Result<(), Vec<Error>> {
+  t.check()?;
+
   let mut f = rng.next_f32();
 
   f -= 0.1;
   if f <= 0.0 {
     add(t, rng);
-    return
+    return Ok(())
   }
 
   f -= 0.1;
   if f <= 0.0 {
-    t.rules.push(RHS { actions: vec!(), next: vec!() });
-    return
+    t.rules.push(vec!(RHS::new(vec!(), vec!())));
+    return Ok(())
   }
 
   f -= 0.1;
   if f <= 0.0 {
     remove(t, rng);
-    return
+    return Ok(())
   }
 
   point(t, rng);
+  Ok(())
 }
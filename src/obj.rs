@@ -0,0 +1,48 @@
+//! Export meshes as Wavefront OBJ files, e.g. to open plants in Blender.
+
+use std;
+
+use mesh;
+
+/// Write `mesh` (see `mesh::T::to_hashmap`) as an OBJ file, with one group per texture id, named and using the
+/// material `name(texture_id)`. Names shouldn't contain whitespace. Groups are written in order of their names.
+/// Texture positions are mapped from -1 to 1 to OBJ's 0 to 1.
+pub fn write<TextureId, Name, W>(
+  mesh     : &std::collections::HashMap<TextureId, (Vec<mesh::Vertex>, Vec<u32>)>,
+  mut name : Name,
+  w        : &mut W,
+) -> std::io::Result<()> where
+  TextureId : Eq + std::hash::Hash,
+  Name      : FnMut(&TextureId) -> String,
+  W         : std::io::Write,
+{
+  let mut groups: Vec<(String, &Vec<mesh::Vertex>, &Vec<u32>)> =
+    mesh.iter().map(|(texture_id, &(ref vertices, ref indices))| (name(texture_id), vertices, indices)).collect();
+  groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+  // OBJ indices start at 1, and count every vertex before them in the file.
+  let mut offset = 1;
+  for (name, vertices, indices) in groups {
+    writeln!(w, "g {}", name)?;
+    writeln!(w, "usemtl {}", name)?;
+    for v in vertices {
+      writeln!(w, "v {} {} {}", v.position[0], v.position[1], v.position[2])?;
+    }
+    for v in vertices {
+      writeln!(w, "vt {} {}", (v.texture_posn[0] + 1.0) / 2.0, (v.texture_posn[1] + 1.0) / 2.0)?;
+    }
+    for v in vertices {
+      writeln!(w, "vn {} {} {}", v.normal[0], v.normal[1], v.normal[2])?;
+    }
+    for triangle in indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+      write!(w, "f")?;
+      for &i in triangle {
+        let i = offset + i as usize;
+        write!(w, " {}/{}/{}", i, i, i)?;
+      }
+      writeln!(w)?;
+    }
+    offset += vertices.len();
+  }
+  Ok(())
+}
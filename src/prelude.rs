@@ -3,3 +3,4 @@ use cgmath;
 pub type Point = cgmath::Point2<f32>;
 pub type Vector = cgmath::Vector2<f32>;
 pub type Matrix = cgmath::Matrix3<f32>;
+pub type Matrix4 = cgmath::Matrix4<f32>;
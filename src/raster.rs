@@ -0,0 +1,170 @@
+//! Render vertices into RGBA images on the CPU, e.g. for thumbnails and golden-image tests without a GPU.
+
+use png;
+use std;
+
+use vertex;
+
+/// Maps screen positions to pixel positions.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+  pub scale  : f32,
+  pub offset : [f32; 2],
+}
+
+impl View {
+  /// Scale and center `vertices` (see `vertices::T::to_hashmap`) to fill a `width` by `height` image, keeping
+  /// their aspect ratio.
+  pub fn fit<TextureId: Eq + std::hash::Hash>(
+    vertices : &std::collections::HashMap<TextureId, Vec<vertex::T>>,
+    width    : u32,
+    height   : u32,
+  ) -> View {
+    let mut min_x = std::f32::INFINITY;
+    let mut min_y = std::f32::INFINITY;
+    let mut max_x = std::f32::NEG_INFINITY;
+    let mut max_y = std::f32::NEG_INFINITY;
+    for vertex in vertices.values().flat_map(|vertices| vertices.iter()) {
+      min_x = min_x.min(vertex.screen_posn[0]);
+      min_y = min_y.min(vertex.screen_posn[1]);
+      max_x = max_x.max(vertex.screen_posn[0]);
+      max_y = max_y.max(vertex.screen_posn[1]);
+    }
+    if min_x > max_x {
+      return View { scale: 1.0, offset: [0.0, 0.0] }
+    }
+
+    let scale = (width as f32 / (max_x - min_x)).min(height as f32 / (max_y - min_y));
+    View {
+      scale  : scale,
+      offset : [
+        width as f32 / 2.0 - scale * (min_x + max_x) / 2.0,
+        height as f32 / 2.0 - scale * (min_y + max_y) / 2.0,
+      ],
+    }
+  }
+
+  /// Pixel positions have y pointing down.
+  fn apply(&self, screen_posn: [f32; 2], height: u32) -> [f32; 2] {
+    [
+      self.scale * screen_posn[0] + self.offset[0],
+      height as f32 - (self.scale * screen_posn[1] + self.offset[1]),
+    ]
+  }
+}
+
+/// An RGBA image.
+pub struct T {
+  pub width  : u32,
+  pub height : u32,
+  /// Four bytes per pixel, row by row from the top left.
+  pub pixels : Vec<u8>,
+}
+
+pub fn new(width: u32, height: u32, background: [u8; 4]) -> T {
+  T {
+    width  : width,
+    height : height,
+    pixels : (0 .. width * height).flat_map(|_| background.iter().cloned()).collect(),
+  }
+}
+
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+  (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Whether pixels exactly on the edge from `a` to `b` belong to its triangle, so that pixels on an edge shared by
+/// two triangles are only drawn once.
+fn owns_edge(a: [f32; 2], b: [f32; 2]) -> bool {
+  b[1] > a[1] || (b[1] == a[1] && b[0] > a[0])
+}
+
+impl T {
+  fn blend(&mut self, x: u32, y: u32, color: [u8; 4]) {
+    let i = 4 * (y * self.width + x) as usize;
+    let alpha = color[3] as f32 / 255.0;
+    let dst_alpha = self.pixels[i + 3] as f32 / 255.0;
+    let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+    if out_alpha > 0.0 {
+      for c in 0 .. 3 {
+        let blended = (color[c] as f32 * alpha + self.pixels[i + c] as f32 * dst_alpha * (1.0 - alpha)) / out_alpha;
+        self.pixels[i + c] = blended.round() as u8;
+      }
+    }
+    self.pixels[i + 3] = (out_alpha * 255.0).round() as u8;
+  }
+
+  /// Fill a list of triangles, blending in `color(texture_posn)` at every pixel center they cover.
+  pub fn draw_triangles<Color>(&mut self, view: &View, triangles: &[vertex::T], mut color: Color) where
+    Color: FnMut([f32; 2]) -> [u8; 4],
+  {
+    for triangle in triangles.chunks(3).filter(|triangle| triangle.len() == 3) {
+      let mut v = [&triangle[0], &triangle[1], &triangle[2]];
+      let mut p = [
+        view.apply(v[0].screen_posn, self.height),
+        view.apply(v[1].screen_posn, self.height),
+        view.apply(v[2].screen_posn, self.height),
+      ];
+      let mut area = edge(p[0], p[1], p[2]);
+      if area == 0.0 || !area.is_finite() {
+        continue
+      }
+      if area < 0.0 {
+        v.swap(1, 2);
+        p.swap(1, 2);
+        area = -area;
+      }
+
+      let min_x = p.iter().map(|p| p[0]).fold(std::f32::INFINITY, f32::min).floor().max(0.0) as u32;
+      let min_y = p.iter().map(|p| p[1]).fold(std::f32::INFINITY, f32::min).floor().max(0.0) as u32;
+      let max_x = p.iter().map(|p| p[0]).fold(std::f32::NEG_INFINITY, f32::max).ceil().min(self.width as f32) as u32;
+      let max_y = p.iter().map(|p| p[1]).fold(std::f32::NEG_INFINITY, f32::max).ceil().min(self.height as f32) as u32;
+
+      for y in min_y .. max_y {
+        for x in min_x .. max_x {
+          let center = [x as f32 + 0.5, y as f32 + 0.5];
+          // The weight of each vertex is the area of the triangle opposite it.
+          let w = [edge(p[1], p[2], center), edge(p[2], p[0], center), edge(p[0], p[1], center)];
+          let inside = |w: f32, a: [f32; 2], b: [f32; 2]| w > 0.0 || (w == 0.0 && owns_edge(a, b));
+          if !(inside(w[0], p[1], p[2]) && inside(w[1], p[2], p[0]) && inside(w[2], p[0], p[1])) {
+            continue
+          }
+
+          let mut texture_posn = [0.0, 0.0];
+          for i in 0 .. 3 {
+            texture_posn[0] += w[i] / area * v[i].texture_posn[0];
+            texture_posn[1] += w[i] / area * v[i].texture_posn[1];
+          }
+          let color = color(texture_posn);
+          self.blend(x, y, color);
+        }
+      }
+    }
+  }
+
+  /// Draw `vertices` (see `vertices::T::to_hashmap`) scaled to fit the image, coloring each pixel with
+  /// `color(texture_id, texture_posn)`. Textures are drawn in order of their ids, so the last one ends up on top.
+  pub fn draw<TextureId, Color>(
+    &mut self,
+    vertices  : &std::collections::HashMap<TextureId, Vec<vertex::T>>,
+    mut color : Color,
+  ) where
+    TextureId : Eq + std::hash::Hash + Ord,
+    Color     : FnMut(&TextureId, [f32; 2]) -> [u8; 4],
+  {
+    let view = View::fit(vertices, self.width, self.height);
+    let mut textures: Vec<(&TextureId, &Vec<vertex::T>)> = vertices.iter().collect();
+    textures.sort_by(|a, b| a.0.cmp(b.0));
+    for (texture_id, triangles) in textures {
+      self.draw_triangles(&view, triangles, |texture_posn| color(texture_id, texture_posn));
+    }
+  }
+
+  pub fn write_png<W: std::io::Write>(&self, w: W) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(w, self.width, self.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&self.pixels)
+  }
+}
@@ -1,8 +1,10 @@
 use cgmath;
+use cgmath::InnerSpace;
 use std;
 
 use prelude::*;
 use alphabet::*;
+use mesh;
 use vertices;
 use word;
 use vertex;
@@ -14,52 +16,535 @@ fn translate(v: &Vector) -> Matrix {
   transform
 }
 
+fn cross(o: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+  (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Triangulate a simple polygon by ear clipping, returning indices into `points`. Whatever's left if the polygon
+/// isn't simple is triangulated as a fan.
+fn triangulate(points: &[[f32; 2]]) -> Vec<u32> {
+  let area: f32 = (0 .. points.len()).map(|i| cross([0.0, 0.0], points[i], points[(i + 1) % points.len()])).sum();
+  let mut remaining: Vec<usize> = (0 .. points.len()).collect();
+  if area < 0.0 {
+    // Make it counterclockwise.
+    remaining.reverse();
+  }
+
+  let mut indices = vec!();
+  while remaining.len() > 3 {
+    let n = remaining.len();
+    let is_ear = |i: usize| {
+      let (a, b, c) = (points[remaining[(i + n - 1) % n]], points[remaining[i]], points[remaining[(i + 1) % n]]);
+      cross(a, b, c) > 0.0 &&
+      remaining.iter().enumerate()
+        .filter(|&(j, _)| j != i && j != (i + n - 1) % n && j != (i + 1) % n)
+        .all(|(_, &k)| {
+          let p = points[k];
+          !(cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0)
+        })
+    };
+    match (0 .. n).find(|&i| is_ear(i)) {
+      None => break,
+      Some(i) => {
+        indices.extend_from_slice(&[remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]]);
+        remaining.remove(i);
+      },
+    }
+  }
+  for i in 1 .. remaining.len().saturating_sub(1) {
+    indices.extend_from_slice(&[remaining[0], remaining[i], remaining[i + 1]]);
+  }
+
+  indices.into_iter().map(|i| i as u32).collect()
+}
+
+/// Texture positions for a polygon's points, mapping its bounding box to -1 to 1 like branches.
+fn polygon_texture_posns(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+  let mut min = [std::f32::INFINITY; 2];
+  let mut max = [std::f32::NEG_INFINITY; 2];
+  for p in points {
+    for i in 0 .. 2 {
+      min[i] = min[i].min(p[i]);
+      max[i] = max[i].max(p[i]);
+    }
+  }
+  let texture_posn = |p: &[f32; 2], i: usize| {
+    if max[i] > min[i] { 2.0 * (p[i] - min[i]) / (max[i] - min[i]) - 1.0 } else { 0.0 }
+  };
+  points.iter().map(|p| [texture_posn(p, 0), texture_posn(p, 1)]).collect()
+}
+
+/// Visit every atom in `word` in the same order as a recursive traversal, but without recursing, so that deep
+/// words can't overflow the stack. Each atom is visited with a copy of the state its parent was left in.
+fn visit_atoms<Texture, State, Visit>(word: &word::T<Texture>, state: State, mut visit: Visit) where
+  State : Clone,
+  Visit : FnMut(&word::Atom<Texture>, &mut State),
+{
+  let mut stack = vec!((word.iter(), state));
+  loop {
+    let next =
+      match stack.last_mut() {
+        None => return,
+        Some(&mut (ref mut atoms, ref state)) => atoms.next().map(|atom| (atom, state.clone())),
+      };
+    match next {
+      None => {
+        stack.pop();
+      },
+      Some((atom, mut state)) => {
+        visit(atom, &mut state);
+        stack.push((atom.subword.iter(), state));
+      },
+    }
+  }
+}
+
+/// How to fill the gaps where one branch follows another at an angle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JointStyle {
+  None,
+  /// Cover the pivot with a disc as wide as the first branch's end, made of `segments` triangles.
+  Round { segments: u32 },
+  /// Extend the outer edges until they meet, or bevel if that's too far away.
+  Miter,
+  /// Join the outer corners with a straight edge.
+  Bevel,
+}
+
+impl Default for JointStyle {
+  fn default() -> Self {
+    JointStyle::None
+  }
+}
+
+/// How far a miter can stick out, relative to half the width of the branches it joins.
+const MITER_LIMIT: f32 = 4.0;
+
+/// The most a bent branch turns within one of its quads, unless `RenderOptions::curve_segments` says otherwise.
+const DEFAULT_CURVE_ANGLE: f32 = std::f32::consts::PI / 36.0;
+
+/// The most quads a bent branch is drawn as by default: a full turn's worth. Branches that bend further than that
+/// turn more within each quad.
+const MAX_CURVE_SEGMENTS: u32 = 72;
+
+/// How many sides branches have in 3D, unless `RenderOptions::sides` says otherwise.
+const DEFAULT_SIDES: u32 = 8;
+
+/// Ways to render a word.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+  /// Store the vertices indexed (see `vertices::new_indexed`).
+  pub indexed        : bool,
+  /// Start each branch as wide as the previous one (or its parent's last one) ended, so limbs are continuous.
+  /// Only the first branch's width is used; the others' tapers still apply.
+  pub continuous     : bool,
+  pub joints         : JointStyle,
+  /// How many quads to draw each bent branch as. If 0, there's one for every 5 degrees of bend, up to 72.
+  pub curve_segments : u32,
+  /// How many sides branches have in `render_mesh`. If 0, they have 8.
+  pub sides          : u32,
+}
+
+/// How many pieces to draw a branch that bends through `bend` as.
+fn curve_segments(bend: f32, options: &RenderOptions) -> u32 {
+  if bend == 0.0 {
+    1
+  } else if options.curve_segments > 0 {
+    options.curve_segments
+  } else {
+    let segments = std::cmp::min((bend.abs() / DEFAULT_CURVE_ANGLE).ceil() as u32, MAX_CURVE_SEGMENTS);
+    std::cmp::max(segments, 1)
+  }
+}
+
 pub fn render<Texture: Clone + Eq + std::hash::Hash>(word: &word::T<Texture>) -> vertices::T<Texture> {
-  let mut vertices = vertices::new();
-  let transform = cgmath::SquareMatrix::from_value(1.0);
-  render_inner(word, &transform, &mut vertices);
+  render_with(word, &Default::default())
+}
+
+/// Like `render`, but the vertices are indexed (see `vertices::new_indexed`).
+pub fn render_indexed<Texture: Clone + Eq + std::hash::Hash>(word: &word::T<Texture>) -> vertices::T<Texture> {
+  render_with(word, &RenderOptions { indexed: true, .. Default::default() })
+}
+
+pub fn render_with<Texture: Clone + Eq + std::hash::Hash>(
+  word    : &word::T<Texture>,
+  options : &RenderOptions,
+) -> vertices::T<Texture> {
+  let mut vertices = if options.indexed { vertices::new_indexed() } else { vertices::new() };
+  let state =
+    State {
+      transform   : cgmath::SquareMatrix::from_value(1.0),
+      end_width   : None,
+      last_branch : None,
+    };
+  visit_atoms(word, state, |atom, state| render_atom(atom, options, state, &mut vertices));
   vertices
 }
 
-fn render_inner<Texture: Clone + Eq + std::hash::Hash>(
-  word      : &word::T<Texture>,
-  transform : &Matrix,
-  vertices  : &mut vertices::T<Texture>,
+/// The end of a branch.
+#[derive(Clone)]
+struct BranchEnd<Texture> {
+  texture_id : Texture,
+  /// The transform at the end of the branch.
+  transform  : Matrix,
+  /// Half the width of the end of the branch, before `transform`.
+  half_width : f32,
+}
+
+#[derive(Clone)]
+struct State<Texture> {
+  transform   : Matrix,
+  /// The screen width of the end of the last branch, for continuous limbs.
+  end_width   : Option<f32>,
+  /// The branch that the next one will continue from, if nothing has moved since.
+  last_branch : Option<BranchEnd<Texture>>,
+}
+
+fn point(transform: &Matrix, x: f32, y: f32) -> [f32; 2] {
+  let p = transform * cgmath::Vector3::new(x, y, 1.0);
+  [p.x, p.y]
+}
+
+fn direction(transform: &Matrix) -> [f32; 2] {
+  let d = transform * cgmath::Vector3::new(0.0, 1.0, 0.0);
+  [d.x, d.y]
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+  ((a[0] - b[0]) * (a[0] - b[0]) + (a[1] - b[1]) * (a[1] - b[1])).sqrt()
+}
+
+/// Where the line through `a` in direction `da` meets the line through `b` in direction `db`, if it's ahead of `a`.
+fn intersect(a: [f32; 2], da: [f32; 2], b: [f32; 2], db: [f32; 2]) -> Option<[f32; 2]> {
+  let denominator = da[0] * db[1] - da[1] * db[0];
+  if denominator.abs() < 1e-6 {
+    return None
+  }
+  let s = ((b[0] - a[0]) * db[1] - (b[1] - a[1]) * db[0]) / denominator;
+  if s < 0.0 {
+    return None
+  }
+  Some([a[0] + s * da[0], a[1] + s * da[1]])
+}
+
+/// Fill the gap between the end of one branch and the start of the next one, which starts at `start` and is
+/// `2 * half_width` wide.
+fn add_joint<Texture: Clone + Eq + std::hash::Hash>(
+  style      : JointStyle,
+  end        : &BranchEnd<Texture>,
+  start      : &Matrix,
+  half_width : f32,
+  vertices   : &mut vertices::T<Texture>,
 ) {
-  for atom in word {
-    let mut transform = transform.clone();
-
-    for action in &atom.actions {
-      match action {
-        &Terminal::Transform(ref t) => {
-          transform = transform * t.to_matrix();
-        },
-        &Terminal::AddBranch { ref texture_id, width, length } => {
-          let drop_z = |p: cgmath::Vector3<f32>| { [ p.x, p.y ] };
-
-          let x1 = width / 2.0;
-          let x0 = -x1;
-          let y0 = 0.0;
-          let y1 = length;
-
-          let v1 = transform * cgmath::Vector3::new(x0, y0, 1.0);
-          let v2 = transform * cgmath::Vector3::new(x1, y0, 1.0);
-          let v3 = transform * cgmath::Vector3::new(x1, y1, 1.0);
-          let v4 = transform * cgmath::Vector3::new(x0, y1, 1.0);
-
-          vertices.push(texture_id.clone(), vertex::T { screen_posn: drop_z(v2), texture_posn: [ 1.0, -1.0] });
-          vertices.push(texture_id.clone(), vertex::T { screen_posn: drop_z(v1), texture_posn: [-1.0, -1.0] });
-          vertices.push(texture_id.clone(), vertex::T { screen_posn: drop_z(v3), texture_posn: [ 1.0,  1.0] });
-
-          vertices.push(texture_id.clone(), vertex::T { screen_posn: drop_z(v3), texture_posn: [ 1.0,  1.0] });
-          vertices.push(texture_id.clone(), vertex::T { screen_posn: drop_z(v1), texture_posn: [-1.0, -1.0] });
-          vertices.push(texture_id.clone(), vertex::T { screen_posn: drop_z(v4), texture_posn: [-1.0,  1.0] });
-
-          transform = transform * translate(&Vector::new(0.0, length));
-        },
+  // Joints continue the texture across the end of the first branch.
+  let vertex = |p: [f32; 2], x: f32| vertex::T { screen_posn: p, texture_posn: [x, 1.0] };
+  let center = point(&end.transform, 0.0, 0.0);
+
+  match style {
+    JointStyle::None => {},
+    JointStyle::Round { segments } => {
+      let segments = std::cmp::max(segments, 3);
+      let mut disc = vec!(vertex(center, 0.0));
+      let mut indices = vec!();
+      for i in 0 .. segments {
+        let (s, c) = (2.0 * std::f32::consts::PI * i as f32 / segments as f32).sin_cos();
+        disc.push(vertex(point(&end.transform, c * end.half_width, s * end.half_width), c));
+        indices.extend_from_slice(&[0, i + 1, (i + 1) % segments + 1]);
       }
+      vertices.push_indexed(end.texture_id.clone(), &disc, &indices);
+    },
+    JointStyle::Miter | JointStyle::Bevel => {
+      let (end_direction, start_direction) = (direction(&end.transform), direction(start));
+      for &side in &[-1.0, 1.0] {
+        let a = point(&end.transform, side * end.half_width, 0.0);
+        let b = point(start, side * half_width, 0.0);
+        // Only the outer corner, the one behind the start of the second branch, leaves a gap.
+        if (a[0] - center[0]) * start_direction[0] + (a[1] - center[1]) * start_direction[1] >= 0.0 {
+          continue
+        }
+        let miter =
+          if style == JointStyle::Miter {
+            intersect(a, end_direction, b, start_direction)
+              .filter(|&m| distance(m, center) <= MITER_LIMIT * distance(a, center).max(distance(b, center)))
+          } else {
+            None
+          };
+        match miter {
+          None => {
+            vertices.push_indexed(
+              end.texture_id.clone(),
+              &[vertex(center, 0.0), vertex(a, side), vertex(b, side)],
+              &[0, 1, 2],
+            );
+          },
+          Some(m) => {
+            vertices.push_indexed(
+              end.texture_id.clone(),
+              &[vertex(center, 0.0), vertex(a, side), vertex(m, side), vertex(b, side)],
+              &[0, 1, 2, 0, 2, 3],
+            );
+          },
+        }
+      }
+    },
+  }
+}
+
+fn render_atom<Texture: Clone + Eq + std::hash::Hash>(
+  atom     : &word::Atom<Texture>,
+  options  : &RenderOptions,
+  state    : &mut State<Texture>,
+  vertices : &mut vertices::T<Texture>,
+) {
+  let mut stack = vec!();
+
+  for action in &atom.actions {
+    let transform = state.transform;
+    match action {
+      &Terminal::Transform(ref t) => {
+        state.transform = transform * t.to_matrix();
+      },
+      &Terminal::AddBranch { ref texture_id, width, length, taper, bend } => {
+        let x_scale = (transform * cgmath::Vector3::new(1.0, 0.0, 0.0)).magnitude();
+        let width =
+          match state.end_width {
+            Some(end_width) if options.continuous && x_scale > 0.0 => end_width / x_scale,
+            _ => width,
+          };
+
+        if let Some(ref end) = state.last_branch {
+          add_joint(options.joints, end, &transform, width / 2.0, vertices);
+        }
+
+        // A strip of quads along the branch, with both texture coordinates from -1 to 1 like an unbent one.
+        let segments = curve_segments(bend, options);
+        let mut strip = vec!();
+        let mut indices = vec!();
+        for i in 0 .. segments + 1 {
+          let u = i as f32 / segments as f32;
+          // Corners of straight branches are transformed directly, so they come out the same to the last bit as
+          // they did before branches could bend.
+          let (frame, along) =
+            if bend == 0.0 {
+              (transform, length * u)
+            } else {
+              (transform * along_branch(length, bend, u), 0.0)
+            };
+          let x = width / 2.0 * (1.0 + (taper - 1.0) * u);
+          let y = 2.0 * u - 1.0;
+          strip.push(vertex::T { screen_posn: point(&frame,  x, along), texture_posn: [ 1.0, y] });
+          strip.push(vertex::T { screen_posn: point(&frame, -x, along), texture_posn: [-1.0, y] });
+          if i > 0 {
+            let j = 2 * i;
+            indices.extend_from_slice(&[j - 2, j - 1, j, j, j - 1, j + 1]);
+          }
+        }
+        vertices.push_indexed(texture_id.clone(), &strip, &indices);
+
+        state.transform = transform * along_branch(length, bend, 1.0);
+        state.end_width = Some(width * taper * x_scale);
+        state.last_branch =
+          Some(BranchEnd {
+            texture_id : texture_id.clone(),
+            transform  : state.transform,
+            half_width : width / 2.0 * taper,
+          });
+      },
+      &Terminal::Polygon { ref texture_id, ref points } => {
+        let polygon: Vec<vertex::T> =
+          points.iter().zip(polygon_texture_posns(points))
+          .map(|(p, texture_posn)| {
+            vertex::T { screen_posn: point(&transform, p[0], p[1]), texture_posn: texture_posn }
+          })
+          .collect();
+        vertices.push_indexed(texture_id.clone(), &polygon, &triangulate(points));
+      },
+      &Terminal::Move { length } => {
+        state.transform = transform * translate(&Vector::new(0.0, length));
+        state.last_branch = None;
+      },
+      &Terminal::Push => {
+        stack.push(state.clone());
+      },
+      &Terminal::Pop => {
+        if let Some(saved) = stack.pop() {
+          *state = saved;
+        }
+      },
     }
+  }
+}
 
-    render_inner(&atom.subword, &transform, vertices);
+/// Like `render_with`, but in 3D: branches are prisms around the direction they grow in, and polygons are flat
+/// in the plane they're drawn in. `indexed` and `joints` only apply in 2D.
+pub fn render_mesh<Texture: Clone + Eq + std::hash::Hash>(
+  word    : &word::T<Texture>,
+  options : &RenderOptions,
+) -> mesh::T<Texture> {
+  let mut mesh = mesh::new();
+  let state = (cgmath::SquareMatrix::from_value(1.0), None);
+  visit_atoms(word, state, |atom, state| render_mesh_atom(atom, options, state, &mut mesh));
+  mesh
+}
+
+/// Place the branches and polygons in `actions` where `render_mesh` would draw them (but with the widths they're
+/// given, as if `continuous` were off), starting from `transform`. Returns the transform they leave.
+pub fn place<Texture>(
+  actions       : Vec<Terminal<Texture>>,
+  mut transform : Matrix4,
+  placed        : &mut std::collections::VecDeque<word::Placed<Texture>>,
+) -> Matrix4 {
+  let mut stack = vec!();
+
+  for action in actions {
+    match action {
+      Terminal::Transform(t) => {
+        transform = transform * t.to_matrix3d();
+      },
+      Terminal::AddBranch { texture_id, width, length, taper, bend } => {
+        placed.push_back(word::Placed::Branch {
+          texture_id : texture_id,
+          transform  : transform,
+          width      : width,
+          length     : length,
+          taper      : taper,
+          bend       : bend,
+        });
+        transform = transform * to_3d(&along_branch(length, bend, 1.0));
+      },
+      Terminal::Polygon { texture_id, points } => {
+        placed.push_back(word::Placed::Polygon {
+          texture_id : texture_id,
+          transform  : transform,
+          points     : points,
+        });
+      },
+      Terminal::Move { length } => {
+        transform = transform * to_3d(&translate(&Vector::new(0.0, length)));
+      },
+      Terminal::Push => {
+        stack.push(transform);
+      },
+      Terminal::Pop => {
+        if let Some(saved) = stack.pop() {
+          transform = saved;
+        }
+      },
+    }
+  }
+
+  transform
+}
+
+/// A 2D transform as a 3D one that leaves z alone.
+fn to_3d(m: &Matrix) -> Matrix4 {
+  Matrix4::new(
+    m.x.x, m.x.y, 0.0, 0.0,
+    m.y.x, m.y.y, 0.0, 0.0,
+    0.0,   0.0,   1.0, 0.0,
+    m.z.x, m.z.y, 0.0, 1.0,
+  )
+}
+
+/// The matrix to transform normals by so they stay perpendicular to surfaces transformed by `transform`.
+fn normal_matrix(transform: &Matrix4) -> cgmath::Matrix3<f32> {
+  use cgmath::{Matrix, SquareMatrix};
+  let linear =
+    cgmath::Matrix3::new(
+      transform.x.x, transform.x.y, transform.x.z,
+      transform.y.x, transform.y.y, transform.y.z,
+      transform.z.x, transform.z.y, transform.z.z,
+    );
+  linear.invert().map_or(linear, |inverse| inverse.transpose())
+}
+
+fn mesh_vertex(
+  transform    : &Matrix4,
+  normals      : &cgmath::Matrix3<f32>,
+  p            : [f32; 3],
+  n            : [f32; 3],
+  texture_posn : [f32; 2],
+) -> mesh::Vertex {
+  let p = transform * cgmath::Vector4::new(p[0], p[1], p[2], 1.0);
+  let n = (normals * cgmath::Vector3::new(n[0], n[1], n[2])).normalize();
+  mesh::Vertex { position: [p.x, p.y, p.z], normal: [n.x, n.y, n.z], texture_posn: texture_posn }
+}
+
+fn render_mesh_atom<Texture: Clone + Eq + std::hash::Hash>(
+  atom    : &word::Atom<Texture>,
+  options : &RenderOptions,
+  state   : &mut (Matrix4, Option<f32>),
+  mesh    : &mut mesh::T<Texture>,
+) {
+  let (mut transform, mut end_width) = *state;
+  let mut stack = vec!();
+
+  for action in &atom.actions {
+    match action {
+      &Terminal::Transform(ref t) => {
+        transform = transform * t.to_matrix3d();
+      },
+      &Terminal::AddBranch { ref texture_id, width, length, taper, bend } => {
+        let x_scale = (transform * cgmath::Vector4::new(1.0, 0.0, 0.0, 0.0)).magnitude();
+        let width =
+          match end_width {
+            Some(end_width) if options.continuous && x_scale > 0.0 => end_width / x_scale,
+            _ => width,
+          };
+
+        // Rings of vertices along the branch, with the texture's x coordinate going around it from -1 to 1 (so
+        // the first and last vertices in each ring are in the same place) and its y coordinate along it.
+        let sides = if options.sides > 0 { options.sides } else { DEFAULT_SIDES };
+        let segments = curve_segments(bend, options);
+        // How much the sides lean in, which tilts their normals towards the end.
+        let slope = if length > 0.0 { width / 2.0 * (1.0 - taper) / length } else { 0.0 };
+        let mut prism = vec!();
+        let mut indices = vec!();
+        for i in 0 .. segments + 1 {
+          let u = i as f32 / segments as f32;
+          let frame = transform * to_3d(&along_branch(length, bend, u));
+          let normals = normal_matrix(&frame);
+          let r = width / 2.0 * (1.0 + (taper - 1.0) * u);
+          for j in 0 .. sides + 1 {
+            let (s, c) = (2.0 * std::f32::consts::PI * j as f32 / sides as f32).sin_cos();
+            let texture_posn = [2.0 * j as f32 / sides as f32 - 1.0, 2.0 * u - 1.0];
+            prism.push(mesh_vertex(&frame, &normals, [r * c, 0.0, r * s], [c, slope, s], texture_posn));
+            if i > 0 && j > 0 {
+              let b = i * (sides + 1) + j;
+              let a = b - (sides + 1);
+              indices.extend_from_slice(&[a - 1, b - 1, a, a, b - 1, b]);
+            }
+          }
+        }
+        mesh.push_indexed(texture_id.clone(), &prism, &indices);
+
+        transform = transform * to_3d(&along_branch(length, bend, 1.0));
+        end_width = Some(width * taper * x_scale);
+      },
+      &Terminal::Polygon { ref texture_id, ref points } => {
+        let normals = normal_matrix(&transform);
+        let polygon: Vec<mesh::Vertex> =
+          points.iter().zip(polygon_texture_posns(points))
+          .map(|(p, texture_posn)| {
+            mesh_vertex(&transform, &normals, [p[0], p[1], 0.0], [0.0, 0.0, 1.0], texture_posn)
+          })
+          .collect();
+        mesh.push_indexed(texture_id.clone(), &polygon, &triangulate(points));
+      },
+      &Terminal::Move { length } => {
+        transform = transform * to_3d(&translate(&Vector::new(0.0, length)));
+      },
+      &Terminal::Push => {
+        stack.push((transform, end_width));
+      },
+      &Terminal::Pop => {
+        if let Some((saved_transform, saved_end_width)) = stack.pop() {
+          transform = saved_transform;
+          end_width = saved_end_width;
+        }
+      },
+    }
   }
+
+  *state = (transform, end_width);
 }
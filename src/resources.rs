@@ -0,0 +1,126 @@
+//! How growing branches uses up resources, which limits how big `word::generate` lets a plant get.
+
+use std;
+
+/// Decides what branches cost, what children start with, and when growth is cut off.
+///
+/// Each expansion of a nonterminal has its own `Resources`, which starts as `child` of what its parent had left
+/// after its actions. Depth-first (see `word::Order`), that's also after its older siblings' subtrees were added;
+/// breadth-first, it's before any of them were. Once all its children are done, `add_child` decides whether it fits
+/// in what its parent has left, after its older siblings.
+pub trait ResourceModel {
+  /// What's left for an expansion and its descendants to use, and what they've used so far.
+  type Resources: Clone;
+  /// Why there wasn't enough left for something, as counted by `word::Report`.
+  type Shortage: Copy + Eq + std::hash::Hash + std::fmt::Debug;
+
+  /// What the start symbol's expansion starts with.
+  fn initial(&self) -> Self::Resources;
+
+  /// What a child starts with, given what its parent has left. It hasn't used anything yet.
+  fn child(&self, parent: &Self::Resources) -> Self::Resources;
+
+  /// Pay for a branch, given its width at either end and its length, after scaling. Fails if there isn't enough
+  /// left, which cuts off the whole expansion it's in.
+  fn branch(
+    &self,
+    resources   : &mut Self::Resources,
+    start_width : f32,
+    end_width   : f32,
+    length      : f32,
+  ) -> Result<(), Self::Shortage>;
+
+  /// Deduct what a finished `child` used from `parent`. Fails, leaving `parent` as it was, if it used more than
+  /// `parent` can spare, in which case the child is left empty.
+  fn add_child(&self, parent: &mut Self::Resources, child: &Self::Resources) -> Result<(), Self::Shortage>;
+
+  /// Pop back to `saved`, from where the branch being popped was pushed, minus what it's used since.
+  fn pop(&self, resources: &mut Self::Resources, saved: &Self::Resources);
+}
+
+/// Branches use nutrients in proportion to their area, and have mass in proportion to their volume. What can flow
+/// through a branch, and how much it can hold up, are also limited by the square of its narrowest end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NutrientsAndMass {
+  pub available_nutrients : f32,
+  pub max_mass            : f32,
+}
+
+/// What `NutrientsAndMass` keeps track of for each expansion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+  pub available_nutrients : f32,
+  pub max_mass            : f32,
+  pub used_nutrients      : f32,
+  pub mass                : f32,
+}
+
+/// Which of `NutrientsAndMass`'s limits was reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shortage {
+  Nutrients,
+  Mass,
+}
+
+impl ResourceModel for NutrientsAndMass {
+  type Resources = Budget;
+  type Shortage = Shortage;
+
+  fn initial(&self) -> Budget {
+    Budget {
+      available_nutrients : self.available_nutrients,
+      max_mass            : self.max_mass,
+      used_nutrients      : 0.0,
+      mass                : 0.0,
+    }
+  }
+
+  fn child(&self, parent: &Budget) -> Budget {
+    Budget {
+      available_nutrients : parent.available_nutrients,
+      max_mass            : parent.max_mass,
+      used_nutrients      : 0.0,
+      mass                : 0.0,
+    }
+  }
+
+  fn branch(&self, budget: &mut Budget, start_width: f32, end_width: f32, length: f32) -> Result<(), Shortage> {
+    let width = (start_width + end_width) / 2.0;
+    let narrowest = start_width.min(end_width);
+
+    let nutrients_here = length * width * 0.001;
+    budget.used_nutrients += nutrients_here;
+    budget.available_nutrients = budget.available_nutrients.min(narrowest * narrowest) - nutrients_here;
+    if budget.available_nutrients < 0.0 {
+      return Err(Shortage::Nutrients)
+    }
+
+    let mass_here = width * width * length * 0.02;
+    budget.mass += mass_here;
+    budget.max_mass = budget.max_mass.min(narrowest * narrowest) - mass_here;
+    if budget.max_mass < 0.0 {
+      return Err(Shortage::Mass)
+    }
+
+    Ok(())
+  }
+
+  fn add_child(&self, parent: &mut Budget, child: &Budget) -> Result<(), Shortage> {
+    if child.used_nutrients > parent.available_nutrients {
+      return Err(Shortage::Nutrients)
+    }
+    if child.mass > parent.max_mass {
+      return Err(Shortage::Mass)
+    }
+    parent.available_nutrients -= child.used_nutrients;
+    parent.max_mass -= child.mass;
+    parent.used_nutrients += child.used_nutrients;
+    parent.mass += child.mass;
+    Ok(())
+  }
+
+  fn pop(&self, budget: &mut Budget, saved: &Budget) {
+    budget.available_nutrients = saved.available_nutrients - (budget.used_nutrients - saved.used_nutrients);
+    budget.max_mass = saved.max_mass - (budget.mass - saved.mass);
+  }
+}
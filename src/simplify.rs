@@ -0,0 +1,277 @@
+//! Clean up grammars without changing what they generate.
+//!
+//! By default, every expansion is kept and transforms are only dropped when they do nothing, so the same random
+//! numbers and budgets give exactly the same word. The other simplifications are opt-in, in `SimplifyOptions`:
+//! folding transforms together only gives the same transforms up to rounding, which can move where a budget or
+//! `min_scale` cuts generation off, and inlining rules means fewer expansions are needed to reach the same
+//! branches, so words are then only unchanged when `max_recursion` and `max_iterations` aren't what cut them off.
+
+use prelude::*;
+use alphabet::*;
+use expr::Expr;
+use grammar::*;
+
+fn const_transform(t: &Transform<Expr>) -> Option<Transform> {
+  match (t.rotation.as_const(), t.pitch.as_const(), t.roll.as_const(), t.scale.x.as_const(), t.scale.y.as_const()) {
+    (Some(rotation), Some(pitch), Some(roll), Some(x), Some(y)) =>
+      Some(Transform { rotation: rotation, pitch: pitch, roll: roll, scale: Vector::new(x, y) }),
+    _ => None,
+  }
+}
+
+fn is_identity(t: &Transform) -> bool {
+  t.rotation == 0.0 && t.pitch == 0.0 && t.roll == 0.0 && t.scale.x == 1.0 && t.scale.y == 1.0
+}
+
+/// Combine two transforms into one that does the same thing (in 2D and 3D), if possible.
+fn combine(first: &Transform, second: &Transform) -> Option<Transform> {
+  let scale = Vector::new(first.scale.x * second.scale.x, first.scale.y * second.scale.y);
+  // A uniform scale commutes with rotations, and consecutive rotations about the same axis add up.
+  let uniform = first.scale.x == first.scale.y;
+  let (rotation, pitch, roll) =
+    if second.rotation == 0.0 && second.pitch == 0.0 && second.roll == 0.0 {
+      (first.rotation, first.pitch, first.roll)
+    } else if uniform && first.pitch == 0.0 && first.roll == 0.0 {
+      (first.rotation + second.rotation, second.pitch, second.roll)
+    } else if uniform && first.roll == 0.0 && second.rotation == 0.0 {
+      (first.rotation, first.pitch + second.pitch, second.roll)
+    } else if uniform && second.rotation == 0.0 && second.pitch == 0.0 {
+      (first.rotation, first.pitch, first.roll + second.roll)
+    } else {
+      return None
+    };
+  Some(Transform { rotation: rotation, pitch: pitch, roll: roll, scale: scale })
+}
+
+/// Remove constant transforms that do nothing, and if `fold` is set, combine adjacent ones.
+fn fold_transforms<Texture>(actions: &mut Vec<Terminal<Texture, Expr>>, fold: bool) {
+  let mut folded: Vec<Terminal<Texture, Expr>> = vec!();
+  for action in actions.drain(..) {
+    if let Terminal::Transform(ref next) = action {
+      if let Some(next) = const_transform(next) {
+        let prev =
+          match folded.last() {
+            Some(&Terminal::Transform(ref prev)) if fold => const_transform(prev),
+            _ => None,
+          };
+        let combined =
+          match prev {
+            None => Some(next),
+            Some(prev) => {
+              match combine(&prev, &next) {
+                None => Some(next),
+                Some(combined) => {
+                  folded.pop();
+                  Some(combined)
+                },
+              }
+            },
+          };
+        if let Some(combined) = combined {
+          if !is_identity(&combined) {
+            folded.push(Terminal::Transform(combined).into());
+          }
+        }
+        continue
+      }
+    }
+    folded.push(action);
+  }
+  *actions = folded;
+}
+
+enum Trivial {
+  /// The nonterminal never generates anything.
+  Empty,
+  /// The nonterminal just expands to another one, with the given arguments.
+  Alias(Nonterminal, Vec<Expr>),
+}
+
+fn trivial<Texture>(alternatives: &[RHS<Texture>]) -> Option<Trivial> {
+  if alternatives.is_empty() {
+    return Some(Trivial::Empty)
+  }
+  if alternatives.len() > 1 || !alternatives[0].guard.is_empty() {
+    return None
+  }
+
+  let rhs = &alternatives[0];
+  if rhs.weight <= 0.0 || (rhs.actions.is_empty() && rhs.next.is_empty()) {
+    Some(Trivial::Empty)
+  } else if rhs.actions.is_empty() && rhs.next.len() == 1 {
+    Some(Trivial::Alias(rhs.next[0], rhs.args.get(0).cloned().unwrap_or(vec!())))
+  } else {
+    None
+  }
+}
+
+/// Whether inlining could change which rules apply.
+fn is_position_dependent<Texture>(t: &T<Texture>) -> bool {
+  t.rules.iter().flat_map(|alternatives| alternatives.iter()).any(|rhs| {
+    rhs.context != Context::default() ||
+    rhs.guard.iter().any(|c| c.lhs.uses_depth() || c.rhs.uses_depth()) ||
+    rhs.args.iter().flat_map(|args| args.iter()).any(|arg| arg.uses_depth()) ||
+    rhs.actions.iter().any(|action| {
+      match action {
+        &Terminal::Transform(ref t) =>
+          [&t.rotation, &t.pitch, &t.roll, &t.scale.x, &t.scale.y].iter().any(|e| e.uses_depth()),
+        &Terminal::AddBranch { ref width, ref length, ref taper, ref bend, .. } =>
+          width.uses_depth() || length.uses_depth() || taper.uses_depth() || bend.uses_depth(),
+        &Terminal::Polygon { ref points, .. } => points.iter().any(|p| p[0].uses_depth() || p[1].uses_depth()),
+        &Terminal::Move { ref length } => length.uses_depth(),
+        &Terminal::Push | &Terminal::Pop => false,
+      }
+    })
+  })
+}
+
+/// Remove the alternatives of rules that can never be picked. Only lone alternatives are removed: picking between
+/// several uses up randomness, even if they all have no weight.
+fn clear_unused<Texture>(t: &mut T<Texture>) {
+  for alternatives in &mut t.rules {
+    if alternatives.len() == 1 && alternatives[0].weight <= 0.0 {
+      alternatives.clear();
+    }
+  }
+}
+
+/// Replace references to trivial rules. Returns what the start symbol should be replaced with.
+fn inline_trivial<Texture>(t: &mut T<Texture>) -> Nonterminal {
+  // What to replace each nonterminal with, and its arguments in terms of the replaced nonterminal's parameters
+  // (`None` if they're passed through unchanged).
+  let replacements: Vec<Option<(Nonterminal, Option<Vec<Expr>>)>> =
+    (0 .. t.rules.len())
+    .map(|i| {
+      let mut nt = Nonterminal(i as u32);
+      let mut args: Option<Vec<Expr>> = None;
+      let mut seen = vec!(nt);
+      loop {
+        match trivial(&t.rules[nt.0 as usize]) {
+          None => return Some((nt, args)),
+          Some(Trivial::Empty) => return None,
+          Some(Trivial::Alias(next, next_args)) => {
+            // A cycle of aliases just recurses until it runs out of budget, without generating anything.
+            if seen.contains(&next) {
+              return None
+            }
+            seen.push(next);
+            nt = next;
+            args =
+              Some(match args {
+                None => next_args,
+                Some(args) => next_args.iter().map(|arg| arg.substitute(&args)).collect(),
+              });
+          },
+        }
+      }
+    })
+    .collect();
+
+  for rhs in t.rules.iter_mut().flat_map(|alternatives| alternatives.iter_mut()) {
+    let mut next = vec!();
+    let mut args = vec!();
+    for (i, &nt) in rhs.next.iter().enumerate() {
+      if let Some((replacement, ref replacement_args)) = replacements[nt.0 as usize] {
+        let passed = rhs.args.get(i).cloned().unwrap_or(vec!());
+        next.push(replacement);
+        args.push(
+          match replacement_args {
+            &None => passed,
+            &Some(ref replacement_args) => replacement_args.iter().map(|arg| arg.substitute(&passed)).collect(),
+          }
+        );
+      }
+    }
+    rhs.next = next;
+    rhs.args = if args.iter().any(|args| !args.is_empty()) { args } else { vec!() };
+  }
+
+  // The start symbol is expanded without arguments, so its replacement can't take any.
+  match replacements[0] {
+    Some((root, None)) => root,
+    Some((root, Some(ref args))) if args.is_empty() => root,
+    _ => Nonterminal(0),
+  }
+}
+
+/// Remove the rules that can't be reached from `root`, and renumber the rest so that `root` is the start symbol.
+fn remove_unreachable<Texture>(t: &mut T<Texture>, root: Nonterminal) {
+  let mut reachable = vec!(false; t.rules.len());
+  let mut pending = vec!(root);
+  reachable[root.0 as usize] = true;
+  while let Some(nt) = pending.pop() {
+    for rhs in &t.rules[nt.0 as usize] {
+      for &next in &rhs.next {
+        if !reachable[next.0 as usize] {
+          reachable[next.0 as usize] = true;
+          pending.push(next);
+        }
+      }
+    }
+  }
+
+  let order: Vec<usize> =
+    Some(root.0 as usize).into_iter()
+    .chain((0 .. t.rules.len()).filter(|&i| reachable[i] && i != root.0 as usize))
+    .collect();
+  let mut renumbered = vec!(None; t.rules.len());
+  for (new, &old) in order.iter().enumerate() {
+    renumbered[old] = Some(Nonterminal(new as u32));
+  }
+
+  let mut rules: Vec<Option<Vec<RHS<Texture>>>> = t.rules.drain(..).map(Some).collect();
+  for &old in &order {
+    let mut alternatives = rules[old].take().unwrap();
+    // Contexts that refer to unreachable rules can never match.
+    alternatives.retain(|rhs| {
+      let context = rhs.context;
+      context.parent.iter().chain(context.left.iter()).chain(context.right.iter())
+        .all(|nt| renumbered[nt.0 as usize].is_some())
+    });
+    for rhs in &mut alternatives {
+      for nt in &mut rhs.next {
+        *nt = renumbered[nt.0 as usize].unwrap();
+      }
+      let context = &mut rhs.context;
+      for nt in context.parent.iter_mut().chain(context.left.iter_mut()).chain(context.right.iter_mut()) {
+        *nt = renumbered[nt.0 as usize].unwrap();
+      }
+    }
+    t.rules.push(alternatives);
+  }
+}
+
+/// Ways to simplify a grammar.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimplifyOptions {
+  /// Combine adjacent constant transforms into one.
+  pub fold_transforms : bool,
+  /// Inline rules that are empty or just expand to another rule, unless rules depend on where they are.
+  pub inline_rules    : bool,
+}
+
+/// Remove constant transforms that do nothing, alternatives that can never be picked, and rules that can't be
+/// reached from the start symbol. Fails, leaving `t` alone, if the grammar has any fatal errors (see
+/// `grammar::T::check`).
+pub fn simplify<Texture>(t: &mut T<Texture>) -> Result<(), Vec<Error>> {
+  simplify_with(t, &Default::default())
+}
+
+/// Like `simplify`, but with the given options.
+pub fn simplify_with<Texture>(t: &mut T<Texture>, options: &SimplifyOptions) -> Result<(), Vec<Error>> {
+  t.check()?;
+
+  for rhs in t.rules.iter_mut().flat_map(|alternatives| alternatives.iter_mut()) {
+    fold_transforms(&mut rhs.actions, options.fold_transforms);
+  }
+  clear_unused(t);
+
+  let root =
+    if options.inline_rules && !is_position_dependent(t) {
+      inline_trivial(t)
+    } else {
+      Nonterminal(0)
+    };
+  remove_unreachable(t, root);
+  Ok(())
+}
@@ -0,0 +1,80 @@
+//! Export rendered words as SVG documents, e.g. for figures on machines without a GPU.
+
+use std;
+
+use vertex;
+
+fn escape(s: &str) -> String {
+  s.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;")
+}
+
+/// Write `shapes` (see `vertices::T::to_shapes`) as an SVG document whose viewBox fits them exactly.
+/// Each shape, e.g. a branch, becomes one path, and each texture's paths are grouped together. Groups are styled
+/// with `style(texture_id)`, e.g. `"fill:#336619"`, and drawn in order of their styles. Every triangle is wound the
+/// same way, so overlapping ones don't cancel out.
+pub fn write<TextureId, Style, W>(
+  shapes    : &std::collections::HashMap<TextureId, Vec<Vec<vertex::T>>>,
+  mut style : Style,
+  w         : &mut W,
+) -> std::io::Result<()> where
+  TextureId : Eq + std::hash::Hash,
+  Style     : FnMut(&TextureId) -> String,
+  W         : std::io::Write,
+{
+  // SVG's y axis points down.
+  let posn = |v: &vertex::T| (v.screen_posn[0], 0.0 - v.screen_posn[1]);
+
+  let mut min_x = std::f32::INFINITY;
+  let mut min_y = std::f32::INFINITY;
+  let mut max_x = std::f32::NEG_INFINITY;
+  let mut max_y = std::f32::NEG_INFINITY;
+  for vertex in shapes.values().flat_map(|shapes| shapes.iter()).flat_map(|vertices| vertices.iter()) {
+    let (x, y) = posn(vertex);
+    min_x = min_x.min(x);
+    min_y = min_y.min(y);
+    max_x = max_x.max(x);
+    max_y = max_y.max(y);
+  }
+  if min_x > max_x {
+    min_x = 0.0;
+    min_y = 0.0;
+    max_x = 1.0;
+    max_y = 1.0;
+  }
+
+  writeln!(
+    w,
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+    min_x, min_y, max_x - min_x, max_y - min_y,
+  )?;
+
+  let mut groups: Vec<(String, &Vec<Vec<vertex::T>>)> =
+    shapes.iter().map(|(texture_id, shapes)| (style(texture_id), shapes)).collect();
+  groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+  for (style, shapes) in groups {
+    writeln!(w, "  <g style=\"{}\">", escape(&style))?;
+    for vertices in shapes {
+      write!(w, "    <path d=\"")?;
+      for (i, triangle) in vertices.chunks(3).filter(|triangle| triangle.len() == 3).enumerate() {
+        if i > 0 {
+          write!(w, " ")?;
+        }
+        let mut p = [posn(&triangle[0]), posn(&triangle[1]), posn(&triangle[2])];
+        // The default nonzero fill rule leaves holes where triangles wound in opposite directions overlap.
+        let area = (p[1].0 - p[0].0) * (p[2].1 - p[0].1) - (p[1].1 - p[0].1) * (p[2].0 - p[0].0);
+        if area < 0.0 {
+          p.swap(1, 2);
+        }
+        for (j, &(x, y)) in p.iter().enumerate() {
+          write!(w, "{}{},{} ", if j == 0 { "M" } else { "L" }, x, y)?;
+        }
+        write!(w, "Z")?;
+      }
+      writeln!(w, "\"/>")?;
+    }
+    writeln!(w, "  </g>")?;
+  }
+
+  writeln!(w, "</svg>")
+}
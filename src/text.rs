@@ -0,0 +1,767 @@
+//! A human-readable text format for grammars.
+//!
+//! One rule per line, of the form
+//!   Parent / Left < Name(params) > Right : guard -> terminal(args)* Name(args)*
+//! where the first rule is the start symbol (i.e. `Nonterminal(0)`). The parameter and argument lists can be
+//! left out when empty, as can the context (`Parent /`, `Left <` and `> Right`) and the guard. A rule only applies
+//! when the name was expanded from `Parent`, between `Left` and `Right`, and its guard holds; a guard is a list of
+//! comparisons (`<`, `<=`, `>`, `>=`, `==`, `!=`) joined by `&&`. A name with several applicable rules picks one
+//! of them at random; they can be weighted like `Name -(0.3)-> ...`, and are otherwise weighted 1. A line with
+//! just a name defines it without any rules, so it never expands to anything.
+//! The terminals are
+//!   rotate(degrees)
+//!   scale(s) or scale(x, y)
+//!   transform(degrees, x, y) or transform(degrees, x, y, pitch, roll) (all three angles in degrees)
+//!   pitch(degrees), roll(degrees) (only used in 3D; see `alphabet::Transform`)
+//!   branch(texture, width, length), branch(texture, width, length, taper) (the end width relative to `width`)
+//!     or branch(texture, width, length, taper, degrees) (bent into an arc that turns through `degrees`)
+//!   polygon(texture, x0, y0, x1, y1, x2, y2, ...) (a filled shape, e.g. a leaf)
+//!   move(length) (like branch, but without drawing anything)
+//!   push, pop (save and restore the current transform)
+//! Arguments are arithmetic expressions (`+`, `-`, `*`, `/` and parentheses) over the rule's parameters and
+//! `depth`, the number of expansions since the start symbol.
+//! Anything after a `#` is a comment. For example:
+//!   S -> A(1, 0.2)
+//!   A(l, w) : w < 0.02 -> branch(Leaf, 0.1, 0.1)
+//!   A(l, w) : w >= 0.02 -> branch(Stem, w, l) L(l, w) R(l, w)
+//!   L(l, w) -> rotate(30) A(l * 0.7, w * 0.6)
+//!   R(l, w) -> rotate(-30) A(l * 0.7, w * 0.6)
+
+use cgmath;
+use std;
+
+use alphabet::*;
+use expr::{Comparison, Condition, Expr};
+use grammar;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+  pub line    : usize,
+  pub message : String,
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+impl std::error::Error for Error {}
+
+fn error<X>(line: usize, message: String) -> Result<X, Error> {
+  Err(Error { line: line, message: message })
+}
+
+const TERMINALS: [&'static str; 10] =
+  ["rotate", "pitch", "roll", "scale", "transform", "branch", "polygon", "move", "push", "pop"];
+
+fn is_identifier(s: &str) -> bool {
+  let mut chars = s.chars();
+  match chars.next() {
+    Some(c) if c.is_alphabetic() || c == '_' => {},
+    _ => return false,
+  }
+  chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Split `s`, which starts with an open parenthesis, into the comma-separated arguments inside it and whatever
+/// follows the matching close parenthesis.
+fn split_args<'a>(line: usize, s: &'a str) -> Result<(Vec<&'a str>, &'a str), Error> {
+  let mut args = vec!();
+  let mut depth = 0;
+  let mut start = 1;
+  for (i, c) in s.char_indices() {
+    match c {
+      '(' => depth += 1,
+      ')' => {
+        depth -= 1;
+        if depth == 0 {
+          let last = s[start .. i].trim();
+          if !(args.is_empty() && last.is_empty()) {
+            args.push(last);
+          }
+          return Ok((args, &s[i + 1 ..]))
+        }
+      },
+      ',' if depth == 1 => {
+        args.push(s[start .. i].trim());
+        start = i + 1;
+      },
+      _ => {},
+    }
+  }
+  error(line, "unclosed parenthesis".to_string())
+}
+
+/// A single symbol on the right hand side of a rule, before name resolution.
+struct Symbol<'a> {
+  name : &'a str,
+  args : Option<Vec<&'a str>>,
+}
+
+fn tokenize<'a>(line: usize, mut s: &'a str) -> Result<Vec<Symbol<'a>>, Error> {
+  let mut symbols = vec!();
+  loop {
+    s = s.trim_start();
+    if s.is_empty() {
+      return Ok(symbols)
+    }
+
+    let end = s.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(s.len());
+    let name = &s[.. end];
+    if name.is_empty() {
+      return error(line, format!("unexpected character {:?}", s.chars().next().unwrap()))
+    }
+    s = s[end ..].trim_start();
+
+    if s.starts_with('(') {
+      let (args, rest) = split_args(line, s)?;
+      symbols.push(Symbol { name: name, args: Some(args) });
+      s = rest;
+    } else {
+      symbols.push(Symbol { name: name, args: None });
+    }
+  }
+}
+
+/// A recursive descent parser for expressions.
+struct ExprParser<'a> {
+  line   : usize,
+  s      : &'a str,
+  params : &'a [&'a str],
+}
+
+impl<'a> ExprParser<'a> {
+  fn peek(&mut self) -> Option<char> {
+    self.s = self.s.trim_start();
+    self.s.chars().next()
+  }
+
+  fn sum(&mut self) -> Result<Expr, Error> {
+    let mut e = self.product()?;
+    loop {
+      match self.peek() {
+        Some('+') => { self.s = &self.s[1 ..]; e = Expr::Add(Box::new(e), Box::new(self.product()?)); },
+        Some('-') => { self.s = &self.s[1 ..]; e = Expr::Sub(Box::new(e), Box::new(self.product()?)); },
+        _ => return Ok(e),
+      }
+    }
+  }
+
+  fn product(&mut self) -> Result<Expr, Error> {
+    let mut e = self.unary()?;
+    loop {
+      match self.peek() {
+        Some('*') => { self.s = &self.s[1 ..]; e = Expr::Mul(Box::new(e), Box::new(self.unary()?)); },
+        Some('/') => { self.s = &self.s[1 ..]; e = Expr::Div(Box::new(e), Box::new(self.unary()?)); },
+        _ => return Ok(e),
+      }
+    }
+  }
+
+  fn unary(&mut self) -> Result<Expr, Error> {
+    match self.peek() {
+      Some('-') => {
+        self.s = &self.s[1 ..];
+        match self.unary()? {
+          Expr::Const(c) => Ok(Expr::Const(-c)),
+          e => Ok(Expr::Neg(Box::new(e))),
+        }
+      },
+      Some('(') => {
+        self.s = &self.s[1 ..];
+        let e = self.sum()?;
+        if self.peek() != Some(')') {
+          return error(self.line, "expected `)`".to_string())
+        }
+        self.s = &self.s[1 ..];
+        Ok(e)
+      },
+      Some(c) if c.is_digit(10) || c == '.' => {
+        let end = self.s.find(|c: char| !(c.is_digit(10) || c == '.')).unwrap_or(self.s.len());
+        let number = &self.s[.. end];
+        self.s = &self.s[end ..];
+        match number.parse() {
+          Ok(c) => Ok(Expr::Const(c)),
+          Err(_) => error(self.line, format!("invalid number {:?}", number)),
+        }
+      },
+      Some(c) if c.is_alphabetic() || c == '_' => {
+        let end = self.s.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(self.s.len());
+        let name = &self.s[.. end];
+        self.s = &self.s[end ..];
+        match self.params.iter().position(|p| *p == name) {
+          Some(i) => Ok(Expr::Param(i as u32)),
+          None if name == "depth" => Ok(Expr::Depth),
+          None => error(self.line, format!("unknown parameter {:?}", name)),
+        }
+      },
+      _ => error(self.line, format!("expected an expression, found {:?}", self.s)),
+    }
+  }
+}
+
+fn parse_expr(line: usize, s: &str, params: &[&str]) -> Result<Expr, Error> {
+  let mut parser = ExprParser { line: line, s: s, params: params };
+  let e = parser.sum()?;
+  if parser.peek().is_some() {
+    return error(line, format!("unexpected {:?} in expression", parser.s))
+  }
+  Ok(e)
+}
+
+const COMPARISONS: [(&'static str, Comparison); 6] = [
+  ("<=", Comparison::LessEqual),
+  (">=", Comparison::GreaterEqual),
+  ("==", Comparison::Equal),
+  ("!=", Comparison::NotEqual),
+  ("<", Comparison::Less),
+  (">", Comparison::Greater),
+];
+
+fn parse_guard(line: usize, s: &str, params: &[&str]) -> Result<Vec<Condition>, Error> {
+  let mut guard = vec!();
+  for condition in s.split("&&") {
+    let found = COMPARISONS.iter().filter_map(|&(op, comparison)| condition.find(op).map(|i| (i, op, comparison))).next();
+    match found {
+      None => return error(line, format!("expected a comparison, found {:?}", condition.trim())),
+      Some((i, op, comparison)) => {
+        guard.push(Condition {
+          lhs        : parse_expr(line, &condition[.. i], params)?,
+          comparison : comparison,
+          rhs        : parse_expr(line, &condition[i + op.len() ..], params)?,
+        });
+      },
+    }
+  }
+  Ok(guard)
+}
+
+/// Split the weight off of the end of a rule's left hand side, if there is one.
+fn split_weight(head: &str) -> Option<(&str, f32)> {
+  if !head.ends_with(')') {
+    return None
+  }
+  let open = head.rfind('(')?;
+  if !head[.. open].ends_with('-') {
+    return None
+  }
+  head[open + 1 .. head.len() - 1].trim().parse().ok().map(|weight| (head[.. open - 1].trim(), weight))
+}
+
+/// One degree, in radians.
+const DEGREE: f32 = std::f32::consts::PI / 180.0;
+
+/// Parse an angle in degrees as radians. Numbers are converted in f64, straight from the text, so that every f32
+/// angle has a number that parses back to exactly it (see `print_angle`). Expressions are multiplied by `DEGREE`
+/// without folding it into their constants, so that printing can take it off again.
+fn parse_angle(line: usize, s: &str, params: &[&str]) -> Result<Expr, Error> {
+  let s = s.trim();
+  if s.chars().all(|c| c.is_digit(10) || c == '-' || c == '.') {
+    if let Ok(degrees) = s.parse::<f64>() {
+      return Ok(Expr::Const(degrees.to_radians() as f32))
+    }
+  }
+  match parse_expr(line, s, params)? {
+    Expr::Const(degrees) => Ok(Expr::Const((degrees as f64).to_radians() as f32)),
+    e => Ok(Expr::Mul(Box::new(e), Box::new(Expr::Const(DEGREE)))),
+  }
+}
+
+/// The shortest number of degrees that `parse_angle` turns back into `radians`.
+fn degrees(radians: f32) -> f64 {
+  let degrees = (radians as f64).to_degrees();
+  (1 .. 18)
+    .filter_map(|digits| format!("{:.*e}", digits - 1, degrees).parse::<f64>().ok())
+    .find(|degrees| degrees.to_radians() as f32 == radians)
+    .unwrap_or(degrees)
+}
+
+/// Print an angle in radians as degrees, in a form that `parse_angle` gives back.
+fn print_angle(s: &mut String, radians: &Expr) {
+  use std::fmt::Write;
+
+  match radians {
+    &Expr::Const(c) => write!(s, "{}", degrees(c)).unwrap(),
+    &Expr::Mul(ref e, ref k) if **k == Expr::Const(DEGREE) => print_expr(s, e, 0),
+    e => print_expr(s, &Expr::Mul(Box::new(e.clone()), Box::new(Expr::Const(1.0 / DEGREE))), 0),
+  }
+}
+
+fn parse_terminal<Texture: std::str::FromStr>(
+  line   : usize,
+  name   : &str,
+  args   : &[&str],
+  params : &[&str],
+) -> Result<Terminal<Texture, Expr>, Error> {
+  let expr = |i: usize| parse_expr(line, args[i], params);
+  let texture = || {
+    match args[0].parse() {
+      Ok(texture_id) => Ok(texture_id),
+      Err(_) => error(line, format!("unknown texture {:?}", args[0])),
+    }
+  };
+  match (name, args.len()) {
+    ("rotate", 1) => {
+      Ok(Terminal::Transform(Transform {
+        rotation : parse_angle(line, args[0], params)?,
+        pitch    : Expr::Const(0.0),
+        roll     : Expr::Const(0.0),
+        scale    : cgmath::Vector2::new(Expr::Const(1.0), Expr::Const(1.0)),
+      }))
+    },
+    ("pitch", 1) => {
+      Ok(Terminal::Transform(Transform {
+        rotation : Expr::Const(0.0),
+        pitch    : parse_angle(line, args[0], params)?,
+        roll     : Expr::Const(0.0),
+        scale    : cgmath::Vector2::new(Expr::Const(1.0), Expr::Const(1.0)),
+      }))
+    },
+    ("roll", 1) => {
+      Ok(Terminal::Transform(Transform {
+        rotation : Expr::Const(0.0),
+        pitch    : Expr::Const(0.0),
+        roll     : parse_angle(line, args[0], params)?,
+        scale    : cgmath::Vector2::new(Expr::Const(1.0), Expr::Const(1.0)),
+      }))
+    },
+    ("scale", 1) => {
+      let s = expr(0)?;
+      Ok(Terminal::Transform(Transform {
+        rotation : Expr::Const(0.0),
+        pitch    : Expr::Const(0.0),
+        roll     : Expr::Const(0.0),
+        scale    : cgmath::Vector2::new(s.clone(), s),
+      }))
+    },
+    ("scale", 2) => {
+      Ok(Terminal::Transform(Transform {
+        rotation : Expr::Const(0.0),
+        pitch    : Expr::Const(0.0),
+        roll     : Expr::Const(0.0),
+        scale    : cgmath::Vector2::new(expr(0)?, expr(1)?),
+      }))
+    },
+    ("transform", 3) => {
+      Ok(Terminal::Transform(Transform {
+        rotation : parse_angle(line, args[0], params)?,
+        pitch    : Expr::Const(0.0),
+        roll     : Expr::Const(0.0),
+        scale    : cgmath::Vector2::new(expr(1)?, expr(2)?),
+      }))
+    },
+    ("transform", 5) => {
+      Ok(Terminal::Transform(Transform {
+        rotation : parse_angle(line, args[0], params)?,
+        pitch    : parse_angle(line, args[3], params)?,
+        roll     : parse_angle(line, args[4], params)?,
+        scale    : cgmath::Vector2::new(expr(1)?, expr(2)?),
+      }))
+    },
+    ("branch", 3) => {
+      Ok(Terminal::AddBranch {
+        texture_id : texture()?,
+        width      : expr(1)?,
+        length     : expr(2)?,
+        taper      : Expr::Const(1.0),
+        bend       : Expr::Const(0.0),
+      })
+    },
+    ("branch", 4) => {
+      Ok(Terminal::AddBranch {
+        texture_id : texture()?,
+        width      : expr(1)?,
+        length     : expr(2)?,
+        taper      : expr(3)?,
+        bend       : Expr::Const(0.0),
+      })
+    },
+    ("branch", 5) => {
+      Ok(Terminal::AddBranch {
+        texture_id : texture()?,
+        width      : expr(1)?,
+        length     : expr(2)?,
+        taper      : expr(3)?,
+        bend       : parse_angle(line, args[4], params)?,
+      })
+    },
+    ("polygon", n) if n >= 7 && n % 2 == 1 => {
+      let texture_id = texture()?;
+      let mut points = vec!();
+      for i in 0 .. n / 2 {
+        points.push([expr(2 * i + 1)?, expr(2 * i + 2)?]);
+      }
+      Ok(Terminal::Polygon {
+        texture_id : texture_id,
+        points     : points,
+      })
+    },
+    ("move", 1) => Ok(Terminal::Move { length: expr(0)? }),
+    ("push", 0) => Ok(Terminal::Push),
+    ("pop", 0) => Ok(Terminal::Pop),
+    _ => error(line, format!("wrong number of arguments to {}", name)),
+  }
+}
+
+/// Parse a grammar, returning it along with the names of its nonterminals (indexed by `Nonterminal`).
+pub fn parse_named<Texture: std::str::FromStr>(s: &str) -> Result<(grammar::T<Texture>, Vec<String>), Error> {
+  // Collect the rule names first, so that rules can refer to ones defined later.
+  let mut lines = vec!();
+  let mut names: Vec<String> = vec!();
+  for (i, line) in s.lines().enumerate() {
+    let i = i + 1;
+    let line = line.split('#').next().unwrap().trim();
+    if line.is_empty() {
+      continue
+    }
+
+    let arrow =
+      match line.find("->") {
+        // A rule without any alternatives.
+        None if is_identifier(line) && !TERMINALS.contains(&line) => {
+          if !names.iter().any(|n| n == line) {
+            names.push(line.to_string());
+          }
+          continue
+        },
+        None => return error(i, "expected `->`".to_string()),
+        Some(arrow) => arrow,
+      };
+    let (head, weight) = split_weight(&line[.. arrow]).unwrap_or((line[.. arrow].trim(), 1.0));
+    let (head, guard) =
+      match head.find(':') {
+        None => (head, None),
+        Some(colon) => (head[.. colon].trim(), Some(&head[colon + 1 ..])),
+      };
+    let (parent, head) =
+      match head.find('/') {
+        None => (None, head),
+        Some(slash) => (Some(head[.. slash].trim()), head[slash + 1 ..].trim()),
+      };
+    let (left, head) =
+      match head.find('<') {
+        None => (None, head),
+        Some(lt) => (Some(head[.. lt].trim()), head[lt + 1 ..].trim()),
+      };
+    let (head, right) =
+      match head.find('>') {
+        None => (head, None),
+        Some(gt) => (head[.. gt].trim(), Some(head[gt + 1 ..].trim())),
+      };
+
+    let (name, params) =
+      match head.find('(') {
+        None => (head, vec!()),
+        Some(open) => {
+          let (params, rest) = split_args(i, &head[open ..])?;
+          if !rest.trim().is_empty() {
+            return error(i, format!("unexpected {:?} after parameters", rest))
+          }
+          (head[.. open].trim(), params)
+        },
+      };
+    if !is_identifier(name) || TERMINALS.contains(&name) {
+      return error(i, format!("invalid rule name {:?}", name))
+    }
+    if let Some(param) = params.iter().find(|p| !is_identifier(p)) {
+      return error(i, format!("invalid parameter name {:?}", param))
+    }
+
+    let idx =
+      match names.iter().position(|n| n == name) {
+        Some(idx) => idx,
+        None => {
+          names.push(name.to_string());
+          names.len() - 1
+        },
+      };
+    let guard =
+      match guard {
+        None => vec!(),
+        Some(guard) => parse_guard(i, guard, &params)?,
+      };
+    lines.push((i, idx, weight, guard, [parent, left, right], params, &line[arrow + 2 ..]));
+  }
+
+  if names.is_empty() {
+    return error(0, "no rules".to_string())
+  }
+
+  let mut rules: Vec<Vec<grammar::RHS<Texture>>> = names.iter().map(|_| vec!()).collect();
+  for (i, idx, weight, guard, context, params, rhs) in lines {
+    let mut resolved = [None; 3];
+    for (name, resolved) in context.iter().zip(resolved.iter_mut()) {
+      if let &Some(name) = name {
+        match names.iter().position(|n| n == name) {
+          None => return error(i, format!("undefined nonterminal {:?} in context", name)),
+          Some(idx) => *resolved = Some(grammar::Nonterminal(idx as u32)),
+        }
+      }
+    }
+
+    let mut actions = vec!();
+    let mut next = vec!();
+    let mut args = vec!();
+    for symbol in tokenize(i, rhs)? {
+      if TERMINALS.contains(&symbol.name) {
+        if !next.is_empty() {
+          return error(i, format!("terminal {:?} after a nonterminal", symbol.name))
+        }
+        let symbol_args = symbol.args.unwrap_or(vec!());
+        actions.push(parse_terminal(i, symbol.name, &symbol_args, &params)?);
+        continue
+      }
+
+      match names.iter().position(|n| n == symbol.name) {
+        None => return error(i, format!("undefined nonterminal {:?}", symbol.name)),
+        Some(idx) => next.push(grammar::Nonterminal(idx as u32)),
+      }
+      let mut exprs = vec!();
+      for arg in symbol.args.unwrap_or(vec!()) {
+        exprs.push(parse_expr(i, arg, &params)?);
+      }
+      args.push(exprs);
+    }
+
+    let mut rhs = grammar::RHS::new(actions, next);
+    rhs.weight = weight;
+    rhs.guard = guard;
+    rhs.context =
+      grammar::Context {
+        parent : resolved[0],
+        left   : resolved[1],
+        right  : resolved[2],
+      };
+    // Don't bother storing arguments if there aren't any.
+    if args.iter().any(|args| !args.is_empty()) {
+      rhs.args = args;
+    }
+    rules[idx].push(rhs);
+  }
+
+  Ok((grammar::T { rules: rules }, names))
+}
+
+pub fn parse<Texture: std::str::FromStr>(s: &str) -> Result<grammar::T<Texture>, Error> {
+  parse_named(s).map(|(t, _)| t)
+}
+
+fn precedence(e: &Expr) -> u32 {
+  match e {
+    &Expr::Add(_, _) | &Expr::Sub(_, _) => 1,
+    &Expr::Mul(_, _) | &Expr::Div(_, _) => 2,
+    &Expr::Neg(_) => 3,
+    &Expr::Const(c) if c < 0.0 => 3,
+    &Expr::Const(_) | &Expr::Param(_) | &Expr::Depth => 4,
+  }
+}
+
+/// Print `e`, parenthesized if it binds less tightly than `min_precedence`. Parameters are named `p0`, `p1`, etc.
+fn print_expr(s: &mut String, e: &Expr, min_precedence: u32) {
+  use std::fmt::Write;
+
+  let precedence = precedence(e);
+  if precedence < min_precedence {
+    s.push('(');
+  }
+  match e {
+    &Expr::Const(c) => write!(s, "{}", c).unwrap(),
+    &Expr::Param(i) => write!(s, "p{}", i).unwrap(),
+    &Expr::Depth => s.push_str("depth"),
+    &Expr::Neg(ref e) => {
+      s.push('-');
+      print_expr(s, e, 4);
+    },
+    &Expr::Add(ref l, ref r) | &Expr::Sub(ref l, ref r) | &Expr::Mul(ref l, ref r) | &Expr::Div(ref l, ref r) => {
+      let op =
+        match e {
+          &Expr::Add(_, _) => " + ",
+          &Expr::Sub(_, _) => " - ",
+          &Expr::Mul(_, _) => " * ",
+          _ => " / ",
+        };
+      print_expr(s, l, precedence);
+      s.push_str(op);
+      print_expr(s, r, precedence + 1);
+    },
+  }
+  if precedence < min_precedence {
+    s.push(')');
+  }
+}
+
+fn print_terminal<Texture: std::fmt::Display>(s: &mut String, terminal: &Terminal<Texture, Expr>) {
+  use std::fmt::Write;
+
+  match terminal {
+    &Terminal::Transform(Transform { ref rotation, ref pitch, ref roll, ref scale }) => {
+      let one = Expr::Const(1.0);
+      let zero = Expr::Const(0.0);
+      let is_rotation = scale.x == one && scale.y == one;
+      if *pitch != zero || *roll != zero {
+        if is_rotation && *rotation == zero && *roll == zero {
+          s.push_str("pitch(");
+          print_angle(s, pitch);
+        } else if is_rotation && *rotation == zero && *pitch == zero {
+          s.push_str("roll(");
+          print_angle(s, roll);
+        } else {
+          s.push_str("transform(");
+          print_angle(s, rotation);
+          s.push_str(", ");
+          print_expr(s, &scale.x, 0);
+          s.push_str(", ");
+          print_expr(s, &scale.y, 0);
+          s.push_str(", ");
+          print_angle(s, pitch);
+          s.push_str(", ");
+          print_angle(s, roll);
+        }
+      } else if is_rotation {
+        s.push_str("rotate(");
+        print_angle(s, rotation);
+      } else if *rotation == zero && scale.x == scale.y {
+        s.push_str("scale(");
+        print_expr(s, &scale.x, 0);
+      } else if *rotation == zero {
+        s.push_str("scale(");
+        print_expr(s, &scale.x, 0);
+        s.push_str(", ");
+        print_expr(s, &scale.y, 0);
+      } else {
+        s.push_str("transform(");
+        print_angle(s, rotation);
+        s.push_str(", ");
+        print_expr(s, &scale.x, 0);
+        s.push_str(", ");
+        print_expr(s, &scale.y, 0);
+      }
+      s.push(')');
+    },
+    &Terminal::AddBranch { ref texture_id, ref width, ref length, ref taper, ref bend } => {
+      write!(s, "branch({}, ", texture_id).unwrap();
+      print_expr(s, width, 0);
+      s.push_str(", ");
+      print_expr(s, length, 0);
+      if *taper != Expr::Const(1.0) || *bend != Expr::Const(0.0) {
+        s.push_str(", ");
+        print_expr(s, taper, 0);
+      }
+      if *bend != Expr::Const(0.0) {
+        s.push_str(", ");
+        print_angle(s, bend);
+      }
+      s.push(')');
+    },
+    &Terminal::Polygon { ref texture_id, ref points } => {
+      write!(s, "polygon({}", texture_id).unwrap();
+      for p in points {
+        s.push_str(", ");
+        print_expr(s, &p[0], 0);
+        s.push_str(", ");
+        print_expr(s, &p[1], 0);
+      }
+      s.push(')');
+    },
+    &Terminal::Move { ref length } => {
+      s.push_str("move(");
+      print_expr(s, length, 0);
+      s.push(')');
+    },
+    &Terminal::Push => s.push_str("push"),
+    &Terminal::Pop => s.push_str("pop"),
+  }
+}
+
+/// Print a grammar in the format accepted by `parse`, using `names` for the nonterminals.
+/// `names` must be valid identifiers, one for each rule.
+pub fn print_named<Texture: std::fmt::Display>(t: &grammar::T<Texture>, names: &[String]) -> String {
+  assert_eq!(names.len(), t.rules.len());
+
+  let mut s = String::new();
+  for (name, alternatives) in names.iter().zip(t.rules.iter()) {
+    if alternatives.is_empty() {
+      s.push_str(&format!("{}\n", name));
+    }
+
+    for rhs in alternatives {
+      if let Some(parent) = rhs.context.parent {
+        s.push_str(&format!("{} / ", names[parent.0 as usize]));
+      }
+      if let Some(left) = rhs.context.left {
+        s.push_str(&format!("{} < ", names[left.0 as usize]));
+      }
+      s.push_str(name);
+
+      let arity =
+        rhs.actions.iter()
+        .map(|action| {
+          match action {
+            &Terminal::Transform(ref t) =>
+              [&t.rotation, &t.pitch, &t.roll, &t.scale.x, &t.scale.y].iter().map(|e| e.arity()).max().unwrap_or(0),
+            &Terminal::AddBranch { ref width, ref length, ref taper, ref bend, .. } =>
+              width.arity().max(length.arity()).max(taper.arity()).max(bend.arity()),
+            &Terminal::Polygon { ref points, .. } =>
+              points.iter().map(|p| p[0].arity().max(p[1].arity())).max().unwrap_or(0),
+            &Terminal::Move { ref length } => length.arity(),
+            &Terminal::Push | &Terminal::Pop => 0,
+          }
+        })
+        .chain(rhs.args.iter().flat_map(|args| args.iter()).map(|arg| arg.arity()))
+        .chain(rhs.guard.iter().map(|condition| condition.arity()))
+        .max()
+        .unwrap_or(0);
+      if arity > 0 {
+        let params: Vec<String> = (0 .. arity).map(|i| format!("p{}", i)).collect();
+        s.push_str(&format!("({})", params.join(", ")));
+      }
+      if let Some(right) = rhs.context.right {
+        s.push_str(&format!(" > {}", names[right.0 as usize]));
+      }
+
+      for (i, condition) in rhs.guard.iter().enumerate() {
+        s.push_str(if i == 0 { " : " } else { " && " });
+        print_expr(&mut s, &condition.lhs, 0);
+        let op = COMPARISONS.iter().find(|&&(_, comparison)| comparison == condition.comparison).unwrap().0;
+        s.push_str(&format!(" {} ", op));
+        print_expr(&mut s, &condition.rhs, 0);
+      }
+
+      if rhs.weight == 1.0 {
+        s.push_str(" ->");
+      } else {
+        s.push_str(&format!(" -({})->", rhs.weight));
+      }
+      for action in &rhs.actions {
+        s.push(' ');
+        print_terminal(&mut s, action);
+      }
+      for (i, nt) in rhs.next.iter().enumerate() {
+        s.push(' ');
+        s.push_str(&names[nt.0 as usize]);
+        match rhs.args.get(i) {
+          Some(args) if !args.is_empty() => {
+            s.push('(');
+            for (j, arg) in args.iter().enumerate() {
+              if j > 0 {
+                s.push_str(", ");
+              }
+              print_expr(&mut s, arg, 0);
+            }
+            s.push(')');
+          },
+          _ => {},
+        }
+      }
+      s.push('\n');
+    }
+  }
+  s
+}
+
+/// Print a grammar in the format accepted by `parse`, naming the nonterminals `N0`, `N1`, etc.
+pub fn print<Texture: std::fmt::Display>(t: &grammar::T<Texture>) -> String {
+  let names: Vec<String> = (0 .. t.rules.len()).map(|i| format!("N{}", i)).collect();
+  print_named(t, &names)
+}
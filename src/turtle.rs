@@ -0,0 +1,282 @@
+//! Convert classic turtle L-systems (an axiom plus rewrite rules over `F`, `+`, `-`, `[`, `]`, ...) into grammars.
+//!
+//! The turtle symbols are
+//!   F, G  draw a branch
+//!   f     move as far as a branch without drawing it
+//!   +, -  turn left/right by the given angle
+//!   |     turn around
+//!   &, ^  pitch down/up by the given angle (only used in 3D)
+//!   \, /  roll left/right by the given angle (only used in 3D)
+//!   [, ]  push/pop the turtle state
+//! Symbols with a rule are variables and become nonterminals; any other symbols are ignored.
+//!
+//! Bracketed substrings, and whatever follows them, become children of the rule they appear in. This means a
+//! variable can only be followed by more symbols if it leaves the turtle where it found it (e.g. `X -> [F]`).
+//! `F`, `G` and `f` can be given a growth rule like `F -> FF`; since a grammar's branches are never rewritten,
+//! this is emulated by scaling every generation down instead.
+//! Every nonterminal takes the number of generations left as its parameter, and variables are guarded on it, so
+//! the grammar stops after as many iterations as asked for. Each bracket still nests one level deeper in the
+//! generated word, so `word::generate` needs a `max_recursion` big enough not to cut it off sooner.
+
+use std;
+
+use prelude::*;
+use alphabet::*;
+use expr::{Comparison, Condition, Expr};
+use grammar;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+  DuplicateRule(char),
+  /// A rule rewrites a turtle symbol other than `F`, `G` or `f`.
+  RuleForTurtleSymbol(char),
+  /// A rule for `F`, `G` or `f` isn't just copies of itself, or they grow at different rates.
+  UnsupportedGrowth(char),
+  UnmatchedBracket,
+  /// A variable that moves the turtle is followed by more symbols at the same bracket depth.
+  FollowedVariable(char),
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      &Error::DuplicateRule(c)       => write!(f, "more than one rule for {:?}", c),
+      &Error::RuleForTurtleSymbol(c) => write!(f, "turtle symbol {:?} can't be rewritten", c),
+      &Error::UnsupportedGrowth(c)   => write!(f, "unsupported growth rule for {:?}", c),
+      &Error::UnmatchedBracket       => write!(f, "unmatched bracket"),
+      &Error::FollowedVariable(c)    => write!(f, "{:?} moves the turtle but isn't the last symbol in its branch", c),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+fn is_draw(c: char) -> bool {
+  c == 'F' || c == 'G'
+}
+
+/// Whether `c` moves the turtle forward.
+fn is_forward(c: char) -> bool {
+  is_draw(c) || c == 'f'
+}
+
+fn is_turtle(c: char) -> bool {
+  is_forward(c) || "+-|&^\\/[]".contains(c)
+}
+
+struct Converter<'a, Texture> {
+  variables  : Vec<char>,
+  /// Whether each variable (indexed like `variables`) leaves the turtle where it found it.
+  neutral    : Vec<bool>,
+  angle      : f32,
+  growth     : f32,
+  branch     : &'a Terminal<Texture>,
+  /// How far `f` moves.
+  step       : f32,
+  rules      : Vec<grammar::RHS<Texture>>,
+}
+
+impl<'a, Texture: Clone> Converter<'a, Texture> {
+  fn variable(&self, c: char) -> Option<grammar::Nonterminal> {
+    self.variables.iter().position(|&v| v == c).map(|i| grammar::Nonterminal(i as u32 + 1))
+  }
+
+  /// Yaw, pitch and roll, in degrees.
+  fn rotate(&self, yaw: f32, pitch: f32, roll: f32) -> Terminal<Texture, Expr> {
+    let radians = |degrees: f32| degrees * std::f32::consts::PI / 180.0;
+    Terminal::Transform(Transform {
+      rotation : radians(yaw),
+      pitch    : radians(pitch),
+      roll     : radians(roll),
+      scale    : Vector::new(1.0, 1.0),
+    }).into()
+  }
+
+  /// Convert a sequence of (variable and turtle) symbols into a rule. `generations` is passed to the variables and
+  /// children it produces.
+  fn sequence(&mut self, s: &[char], generations: &Expr) -> Result<grammar::RHS<Texture>, Error> {
+    let mut actions = vec!();
+    let mut next = vec!();
+
+    let mut i = 0;
+    while i < s.len() {
+      let c = s[i];
+      if c == '[' {
+        let close = matching_bracket(s, i)?;
+        if close > i + 1 {
+          next.push(self.child(&s[i + 1 .. close])?);
+        }
+        i = close + 1;
+      } else if c == ']' {
+        return Err(Error::UnmatchedBracket)
+      } else if let Some(nt) = self.variable(c) {
+        if i + 1 < s.len() && !self.neutral[nt.0 as usize - 1] {
+          return Err(Error::FollowedVariable(c))
+        }
+        next.push(nt);
+        i += 1;
+      } else if !next.is_empty() {
+        // Everything after a child starts from the same turtle state the child did.
+        next.push(self.child(&s[i ..])?);
+        break
+      } else {
+        if is_draw(c) {
+          actions.push(self.branch.clone().into());
+        } else if c == 'f' {
+          actions.push(Terminal::Move { length: self.step.into() });
+        } else if c == '+' {
+          actions.push(self.rotate(self.angle, 0.0, 0.0));
+        } else if c == '-' {
+          actions.push(self.rotate(-self.angle, 0.0, 0.0));
+        } else if c == '|' {
+          actions.push(self.rotate(180.0, 0.0, 0.0));
+        } else if c == '&' {
+          actions.push(self.rotate(0.0, -self.angle, 0.0));
+        } else if c == '^' {
+          actions.push(self.rotate(0.0, self.angle, 0.0));
+        } else if c == '\\' {
+          actions.push(self.rotate(0.0, 0.0, self.angle));
+        } else if c == '/' {
+          actions.push(self.rotate(0.0, 0.0, -self.angle));
+        }
+        i += 1;
+      }
+    }
+
+    let mut rhs = grammar::RHS::new(actions, next);
+    rhs.args = rhs.next.iter().map(|_| vec!(generations.clone())).collect();
+    Ok(rhs)
+  }
+
+  /// A child is part of the same generation as its parent, so it passes the generations it's given straight on.
+  fn child(&mut self, s: &[char]) -> Result<grammar::Nonterminal, Error> {
+    if s.len() == 1 {
+      if let Some(nt) = self.variable(s[0]) {
+        return Ok(nt)
+      }
+    }
+
+    let rhs = self.sequence(s, &Expr::Param(0))?;
+    self.rules.push(rhs);
+    Ok(grammar::Nonterminal(self.rules.len() as u32 - 1))
+  }
+}
+
+fn matching_bracket(s: &[char], open: usize) -> Result<usize, Error> {
+  let mut depth = 0;
+  for i in open .. s.len() {
+    if s[i] == '[' {
+      depth += 1;
+    } else if s[i] == ']' {
+      depth -= 1;
+      if depth == 0 {
+        return Ok(i)
+      }
+    }
+  }
+  Err(Error::UnmatchedBracket)
+}
+
+/// Convert an L-system into a grammar whose start symbol is `axiom`, run for `iterations` generations. `angle` is in
+/// degrees, and `branch` is the terminal drawn for `F` and `G` (`f` moves by its length).
+pub fn to_grammar<Texture: Clone>(
+  axiom      : &str,
+  rules      : &[(char, &str)],
+  angle      : f32,
+  branch     : &Terminal<Texture>,
+  iterations : u32,
+) -> Result<grammar::T<Texture>, Error> {
+  let mut variables = vec!();
+  let mut bodies: Vec<Vec<char>> = vec!();
+  let mut growth = None;
+  for (i, &(c, body)) in rules.iter().enumerate() {
+    if rules[.. i].iter().any(|&(d, _)| d == c) {
+      return Err(Error::DuplicateRule(c))
+    }
+
+    if is_forward(c) {
+      let copies = body.chars().count();
+      if copies == 0 || body.chars().any(|d| d != c) || growth.map_or(false, |g| g != copies) {
+        return Err(Error::UnsupportedGrowth(c))
+      }
+      growth = Some(copies);
+    } else if is_turtle(c) {
+      return Err(Error::RuleForTurtleSymbol(c))
+    } else {
+      variables.push(c);
+      bodies.push(body.chars().collect());
+    }
+  }
+
+  // Drop the symbols that don't mean anything.
+  let keep = |c: &char| is_turtle(*c) || variables.contains(c);
+  let axiom: Vec<char> = axiom.chars().filter(&keep).collect();
+  let bodies: Vec<Vec<char>> = bodies.into_iter().map(|b| b.into_iter().filter(&keep).collect()).collect();
+
+  // Find the variables that leave the turtle where they found it, by ruling out ones that don't.
+  let mut neutral = vec!(true; variables.len());
+  loop {
+    let mut changed = false;
+    for i in 0 .. variables.len() {
+      if !neutral[i] {
+        continue
+      }
+
+      let mut depth = 0;
+      for &c in &bodies[i] {
+        let moves =
+          match c {
+            '[' => { depth += 1; false },
+            ']' => { depth -= 1; false },
+            _ if depth > 0 => false,
+            _ => match variables.iter().position(|&v| v == c) {
+              Some(j) => !neutral[j],
+              None => true,
+            },
+          };
+        if moves {
+          neutral[i] = false;
+          changed = true;
+          break
+        }
+      }
+    }
+    if !changed {
+      break
+    }
+  }
+
+  let mut converter =
+    Converter {
+      variables : variables,
+      neutral   : neutral,
+      angle     : angle,
+      growth    : growth.unwrap_or(1) as f32,
+      branch    : branch,
+      step      : match branch { &Terminal::AddBranch { length, .. } | &Terminal::Move { length } => length, _ => 0.0 },
+      // Placeholders for the axiom and the variables.
+      rules     : (0 .. bodies.len() + 1).map(|_| grammar::RHS::new(vec!(), vec!())).collect(),
+    };
+
+  converter.rules[0] = converter.sequence(&axiom, &Expr::Const(iterations as f32))?;
+  let rest = Expr::Sub(Box::new(Expr::Param(0)), Box::new(Expr::Const(1.0)));
+  for (i, body) in bodies.iter().enumerate() {
+    let mut rhs = converter.sequence(body, &rest)?;
+    // Once there are no generations left, variables are just left in the word, drawing nothing.
+    rhs.guard = vec!(Condition { lhs: Expr::Param(0), comparison: Comparison::Greater, rhs: Expr::Const(0.0) });
+    if converter.growth != 1.0 {
+      let s = 1.0 / converter.growth;
+      let scale =
+        Transform {
+          rotation : 0.0,
+          pitch    : 0.0,
+          roll     : 0.0,
+          scale    : Vector::new(s, s),
+        };
+      rhs.actions.insert(0, Terminal::Transform(scale).into());
+    }
+    converter.rules[i + 1] = rhs;
+  }
+
+  Ok(grammar::T { rules: converter.rules.into_iter().map(|rhs| vec!(rhs)).collect() })
+}
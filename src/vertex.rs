@@ -1,4 +1,5 @@
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct T {
   pub screen_posn  : [f32; 2],
   pub texture_posn : [f32; 2],
@@ -2,23 +2,126 @@ use std;
 
 use vertex;
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Triangles {
+  vertices : Vec<vertex::T>,
+  // If this is indexed, the triangles as indices into `vertices`; otherwise `vertices` is a flat list of triangles.
+  indices  : Option<Vec<u32>>,
+  // Where each shape added by `push_indexed` starts, in the flat list of triangles.
+  #[cfg_attr(feature = "serde", serde(default))]
+  shapes   : Vec<u32>,
+}
+
+impl Triangles {
+  /// The triangles as a flat list of vertices.
+  fn flatten(self) -> Vec<vertex::T> {
+    let Triangles { vertices, indices, .. } = self;
+    match indices {
+      None => vertices,
+      Some(indices) => indices.iter().map(|&i| vertices[i as usize]).collect(),
+    }
+  }
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+  feature = "serde",
+  serde(bound(deserialize = "TextureId: Eq + std::hash::Hash + serde::Deserialize<'de>")),
+)]
 pub struct T<TextureId> {
-  // vertices by texture id
-  vertices: std::collections::HashMap<TextureId, Vec<vertex::T>>,
+  indexed: bool,
+  // triangles by texture id
+  vertices: std::collections::HashMap<TextureId, Triangles>,
 }
 
+/// Store triangles as flat lists of vertices.
 pub fn new<TextureId: Eq + std::hash::Hash>() -> T<TextureId> {
   T {
+    indexed: false,
+    vertices: std::collections::HashMap::new(),
+  }
+}
+
+/// Store triangles as unique vertices plus a `u32` index buffer.
+pub fn new_indexed<TextureId: Eq + std::hash::Hash>() -> T<TextureId> {
+  T {
+    indexed: true,
     vertices: std::collections::HashMap::new(),
   }
 }
 
 impl<TextureId: Eq + std::hash::Hash> T<TextureId> {
+  fn triangles(&mut self, texture_id: TextureId) -> &mut Triangles {
+    let indexed = self.indexed;
+    self.vertices.entry(texture_id).or_insert_with(|| {
+      Triangles {
+        vertices : vec!(),
+        indices  : if indexed { Some(vec!()) } else { None },
+        shapes   : vec!(),
+      }
+    })
+  }
+
+  pub fn is_indexed(&self) -> bool {
+    self.indexed
+  }
+
+  /// Add the next vertex of a triangle, as part of the last shape added with this texture.
   pub fn push(&mut self, texture_id: TextureId, vertex: vertex::T) {
-    self.vertices.entry(texture_id).or_insert(vec!()).push(vertex);
+    self.add(texture_id, &[vertex], &[0], false);
   }
 
+  /// Add triangles given as indices into `vertices`, as one shape (e.g. a branch or a polygon).
+  pub fn push_indexed(&mut self, texture_id: TextureId, vertices: &[vertex::T], indices: &[u32]) {
+    self.add(texture_id, vertices, indices, true);
+  }
+
+  fn add(&mut self, texture_id: TextureId, vertices: &[vertex::T], indices: &[u32], new_shape: bool) {
+    let triangles = self.triangles(texture_id);
+    if new_shape {
+      let start = triangles.indices.as_ref().map_or(triangles.vertices.len(), |indices| indices.len());
+      triangles.shapes.push(start as u32);
+    }
+    match triangles.indices {
+      None => triangles.vertices.extend(indices.iter().map(|&i| vertices[i as usize])),
+      Some(ref mut triangle_indices) => {
+        let offset = triangles.vertices.len() as u32;
+        triangle_indices.extend(indices.iter().map(|&i| offset + i));
+        triangles.vertices.extend_from_slice(vertices);
+      },
+    }
+  }
+
+  /// The triangles for each texture id, as flat lists of vertices.
   pub fn to_hashmap(self) -> std::collections::HashMap<TextureId, Vec<vertex::T>> {
-    self.vertices
+    self.vertices.into_iter().map(|(texture_id, triangles)| (texture_id, triangles.flatten())).collect()
+  }
+
+  /// The triangles for each texture id, as a flat list of vertices for each shape that was pushed, in order.
+  pub fn to_shapes(self) -> std::collections::HashMap<TextureId, Vec<Vec<vertex::T>>> {
+    self.vertices.into_iter()
+      .map(|(texture_id, triangles)| {
+        let mut starts: Vec<usize> = triangles.shapes.iter().map(|&start| start as usize).collect();
+        let vertices = triangles.flatten();
+        // Vertices pushed before any shape was started make up one of their own.
+        if starts.first() != Some(&0) {
+          starts.insert(0, 0);
+        }
+        let ends = starts.iter().skip(1).cloned().chain(Some(vertices.len()));
+        let shapes = starts.iter().zip(ends).map(|(&start, end)| vertices[start .. end].to_vec()).collect();
+        (texture_id, shapes)
+      })
+      .collect()
+  }
+
+  /// The triangles for each texture id, as vertices and indices into them. If this isn't indexed, each vertex
+  /// is only used once.
+  pub fn to_indexed_hashmap(self) -> std::collections::HashMap<TextureId, (Vec<vertex::T>, Vec<u32>)> {
+    self.vertices.into_iter()
+      .map(|(texture_id, Triangles { vertices, indices, .. })| {
+        let indices = indices.unwrap_or_else(|| (0 .. vertices.len() as u32).collect());
+        (texture_id, (vertices, indices))
+      })
+      .collect()
   }
 }
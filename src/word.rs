@@ -1,15 +1,32 @@
 use cgmath;
 use cgmath::{InnerSpace, SquareMatrix};
+use rand;
+use std;
 
 use prelude::*;
 use alphabet::*;
 use grammar;
+use render;
+use resources::ResourceModel;
 
+/// `Atom` implements `Drop` so that dropping a deep word doesn't recurse, which means its fields can't be moved out
+/// by destructuring; take them with `std::mem::take` instead.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Atom<Texture> {
   pub actions: Vec<Terminal<Texture>>,
   pub subword: T<Texture>,
 }
 
+// Dropping a deep word recursively could overflow the stack, so flatten it first.
+impl<Texture> Drop for Atom<Texture> {
+  fn drop(&mut self) {
+    let mut atoms = std::mem::take(&mut self.subword);
+    while let Some(mut atom) = atoms.pop() {
+      atoms.append(&mut atom.subword);
+    }
+  }
+}
+
 fn empty<Texture>() -> Atom<Texture> {
   Atom {
     actions: vec!(),
@@ -19,117 +36,771 @@ fn empty<Texture>() -> Atom<Texture> {
 
 pub type T<Texture> = Vec<Atom<Texture>>;
 
-fn generate_inner<Texture: Clone>(
-  t                 : &grammar::T<Texture>,
-  nt                : grammar::Nonterminal,
-  max_recursion     : u32,
-  max_iterations    : &mut u32,
-  min_scale         : f32,
-  mut transform     : Matrix,
-  mut available_nutrients : f32,
-  mut max_mass      : f32,
-) -> (f32, f32, Atom<Texture>)
-{
-  let mut used_nutrients = 0.0;
-  let mut mass = 0.0;
+/// Pick one of the applicable `alternatives` at random, according to their weights.
+fn choose<'a, Texture, Rng: rand::Rng>(
+  alternatives : &'a [grammar::RHS<Texture>],
+  params       : &[f32],
+  depth        : u32,
+  context      : &grammar::Context,
+  rng          : &mut Rng,
+) -> Option<&'a grammar::RHS<Texture>> {
+  let matches = |rhs: &&grammar::RHS<Texture>| {
+    rhs.context.matches(context) && rhs.guard.iter().all(|c| c.holds(params, depth))
+  };
+  // Only the most specific contexts apply.
+  let specificity = alternatives.iter().filter(&matches).map(|rhs| rhs.context.specificity()).max();
+  let applies = |rhs: &&grammar::RHS<Texture>| matches(rhs) && Some(rhs.context.specificity()) == specificity;
+
+  // Don't consume randomness for deterministic rules.
+  let mut applicable = alternatives.iter().filter(&applies);
+  match (applicable.next(), applicable.next()) {
+    (first, None) => return first.filter(|rhs| rhs.weight > 0.0),
+    _ => {},
+  }
+
+  let total: f32 = alternatives.iter().filter(&applies).map(|rhs| rhs.weight.max(0.0)).sum();
+  let mut f = rng.next_f32() * total;
+  for rhs in alternatives.iter().filter(&applies) {
+    let weight = rhs.weight.max(0.0);
+    if f < weight {
+      return Some(rhs)
+    }
+    f -= weight;
+  }
+
+  // Only reachable through rounding error, or if every weight is zero.
+  alternatives.iter().rev().filter(&applies).find(|rhs| rhs.weight > 0.0)
+}
+
+/// Why part of a word wasn't generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cutoff<Shortage> {
+  /// A nonterminal was `max_recursion` deep.
+  MaxRecursion,
+  /// `max_iterations` ran out.
+  MaxIterations,
+  /// None of a nonterminal's rules applied, or they all had zero weight.
+  NoRule,
+  /// A branch would have been narrower or shorter than `min_scale`, which cut off the expansion it was in.
+  MinScale,
+  /// There weren't enough resources left for a branch, which cut off the expansion it was in.
+  Branch(Shortage),
+  /// A finished child used more resources than its parent had left, so it was dropped.
+  Child(Shortage),
+}
+
+/// What happened while generating a word, e.g. to find out why it's smaller than expected.
+#[derive(Debug, Clone)]
+pub struct Report<Model: ResourceModel> {
+  /// How many times each kind of cutoff happened.
+  pub cutoffs    : std::collections::HashMap<Cutoff<Model::Shortage>, u32>,
+  /// How many nonterminals were expanded at each depth, including ones that were dropped once their children
+  /// were done.
+  pub expansions : Vec<u32>,
+  /// What the start symbol's expansion had left, and what it and the rest of the word used, if it generated
+  /// anything.
+  pub resources  : Option<Model::Resources>,
+}
+
+impl<Model: ResourceModel> Report<Model> {
+  fn new() -> Self {
+    Report {
+      cutoffs    : std::collections::HashMap::new(),
+      expansions : vec!(),
+      resources  : None,
+    }
+  }
+
+  fn cut(&mut self, cutoff: Cutoff<Model::Shortage>) {
+    *self.cutoffs.entry(cutoff).or_insert(0) += 1;
+  }
+
+  fn expanded(&mut self, depth: u32) {
+    let depth = depth as usize;
+    if self.expansions.len() <= depth {
+      self.expansions.resize(depth + 1, 0);
+    }
+    self.expansions[depth] += 1;
+  }
+
+  /// How many times `cutoff` happened.
+  pub fn count(&self, cutoff: Cutoff<Model::Shortage>) -> u32 {
+    self.cutoffs.get(&cutoff).cloned().unwrap_or(0)
+  }
+
+  /// The depth of the deepest expansion, if there were any.
+  pub fn deepest(&self) -> Option<u32> {
+    if self.expansions.is_empty() {
+      None
+    } else {
+      Some(self.expansions.len() as u32 - 1)
+    }
+  }
+}
+
+/// A nonterminal that's been expanded, whose children are being generated.
+struct Expansion<'a, Texture: 'a, Resources> {
+  nt            : grammar::Nonterminal,
+  rhs           : &'a grammar::RHS<Texture>,
+  params        : Vec<f32>,
+  depth         : u32,
+  max_recursion : u32,
+  transform     : Matrix,
+  resources     : Resources,
+  /// The evaluated actions, and the children generated so far.
+  atom          : Atom<Texture>,
+}
 
-  if max_recursion == 0 || *max_iterations == 0 {
-    return (0.0, 0.0, empty())
+impl<'a, Texture, Resources> Expansion<'a, Texture, Resources> {
+  /// Add the next child, if there are enough resources left for it. Returns whether it was added.
+  fn add_child<Model>(
+    &mut self,
+    model     : &Model,
+    resources : &Resources,
+    child     : Atom<Texture>,
+    report    : &mut Report<Model>,
+  ) -> bool where
+    Model: ResourceModel<Resources = Resources>,
+  {
+    match model.add_child(&mut self.resources, resources) {
+      Ok(()) => {
+        self.atom.subword.push(child);
+        true
+      },
+      Err(shortage) => {
+        report.cut(Cutoff::Child(shortage));
+        self.atom.subword.push(empty());
+        false
+      },
+    }
+  }
+
+  /// Add an empty child, which doesn't use anything.
+  fn skip_child(&mut self) {
+    self.atom.subword.push(empty());
+  }
+}
+
+/// Pick a rule for `nt` and apply its actions. `None` means it generates nothing (and uses nothing).
+fn expand<'a, Texture: Clone, Model: ResourceModel, Rng: rand::Rng>(
+  t              : &'a grammar::T<Texture>,
+  nt             : grammar::Nonterminal,
+  params         : Vec<f32>,
+  depth          : u32,
+  context        : grammar::Context,
+  max_recursion  : u32,
+  max_iterations : &mut u32,
+  min_scale      : f32,
+  mut transform  : Matrix,
+  model          : &Model,
+  mut resources  : Model::Resources,
+  report         : &mut Report<Model>,
+  rng            : &mut Rng,
+) -> Option<Expansion<'a, Texture, Model::Resources>>
+{
+  if max_recursion == 0 {
+    report.cut(Cutoff::MaxRecursion);
+    return None
+  }
+  if *max_iterations == 0 {
+    report.cut(Cutoff::MaxIterations);
+    return None
   }
 
   *max_iterations -= 1;
 
-  let rhs = &t.rules[nt.0 as usize];
+  let rhs =
+    match choose(&t.rules[nt.0 as usize], &params, depth, &context, rng) {
+      None => {
+        report.cut(Cutoff::NoRule);
+        return None
+      },
+      Some(rhs) => rhs,
+    };
+
+  let actions: Vec<Terminal<Texture>> = rhs.actions.iter().map(|action| action.eval(&params, depth)).collect();
+
+  // Like children, pushed branches start with the resources available where they were pushed, and what they use is
+  // deducted when they're popped.
+  let mut stack = vec!();
 
   // Interesting trick: similar to how we decrease max_recursion by 1 on every recursion,
   // we also divide min_scale by any scale actions we see.
   // This is equivalent to accumulating the scales and checking if it's less than min_scale.
-  for action in &rhs.actions {
+  for action in &actions {
     match action {
       &Terminal::Transform(ref next_transform) => {
         transform = transform * next_transform.to_matrix();
       },
-      &Terminal::AddBranch { mut width, mut length, .. } => {
+      &Terminal::AddBranch { width, mut length, taper, bend, .. } => {
         let x_scale = (transform * cgmath::Vector3::new(1.0, 0.0, 0.0)).magnitude();
         let y_scale = (transform * cgmath::Vector3::new(0.0, 1.0, 0.0)).magnitude();
         if x_scale <= min_scale || y_scale < min_scale {
-          return (0.0, 0.0, empty())
+          report.cut(Cutoff::MinScale);
+          return None
         }
-        width  *= x_scale;
+        let end_width = width * taper * x_scale;
+        let start_width = width * x_scale;
         length *= y_scale;
 
-        // the deductions are proportional to volume/surface area for 3D branches
-
-        let nutrients_here = length * width * 0.001;
-        used_nutrients += nutrients_here;
-        available_nutrients = available_nutrients.min(width * width) - nutrients_here;
-        if available_nutrients < 0.0 {
-          return (0.0, 0.0, empty())
+        if let Err(shortage) = model.branch(&mut resources, start_width, end_width, length) {
+          report.cut(Cutoff::Branch(shortage));
+          return None
         }
 
-        let mass_here = width * width * length * 0.02;
-        mass += mass_here;
-        max_mass = max_mass.min(width * width) - mass_here;
-        if max_mass < 0.0 {
-          return (0.0, 0.0, empty())
+        // Bending turns whatever follows, which changes how it's scaled if the scale isn't uniform.
+        let bend =
+          Transform {
+            rotation : bend,
+            pitch    : 0.0,
+            roll     : 0.0,
+            scale    : Vector::new(1.0, 1.0),
+          };
+        transform = transform * bend.to_matrix();
+      },
+      // Leaves and moving don't grow anything, and translations don't affect the scale.
+      &Terminal::Polygon { .. } | &Terminal::Move { .. } => {},
+      &Terminal::Push => {
+        stack.push((transform, resources.clone()));
+      },
+      &Terminal::Pop => {
+        if let Some((saved_transform, saved_resources)) = stack.pop() {
+          transform = saved_transform;
+          model.pop(&mut resources, &saved_resources);
         }
       },
     }
   }
 
-  let t =
-    Atom {
-      actions : rhs.actions.clone(),
-      subword :
-        rhs.next
-        .iter()
-        .map(|nt| {
-          let (child_nutrients, child_mass, child) =
-            generate_inner(
-              t,
-              *nt,
-              max_recursion - 1,
-              max_iterations,
-              min_scale,
-              transform,
-              available_nutrients,
-              max_mass,
-            );
-          if child_nutrients > available_nutrients || child_mass > max_mass {
-            empty()
-          } else {
-            available_nutrients -= child_nutrients;
-            max_mass -= child_mass;
-            used_nutrients += child_nutrients;
-            mass += child_mass;
-            child
-          }
-        })
-        .collect(),
+  report.expanded(depth);
+  Some(Expansion {
+    nt            : nt,
+    rhs           : rhs,
+    params        : params,
+    depth         : depth,
+    max_recursion : max_recursion,
+    transform     : transform,
+    resources     : resources,
+    atom          : Atom { actions: actions, subword: vec!() },
+  })
+}
+
+fn expand_root<'a, Texture: Clone, Model: ResourceModel, Rng: rand::Rng>(
+  t              : &'a grammar::T<Texture>,
+  max_recursion  : u32,
+  max_iterations : &mut u32,
+  min_scale      : f32,
+  model          : &Model,
+  report         : &mut Report<Model>,
+  rng            : &mut Rng,
+) -> Option<Expansion<'a, Texture, Model::Resources>> {
+  expand(
+    t,
+    grammar::Nonterminal(0),
+    vec!(),
+    0,
+    Default::default(),
+    max_recursion,
+    max_iterations,
+    min_scale,
+    Matrix::from_value(1.0),
+    model,
+    model.initial(),
+    report,
+    rng,
+  )
+}
+
+/// Expand `parent`'s `i`th child, with the transform and resources `parent` has left.
+fn expand_child<'a, Texture: Clone, Model: ResourceModel, Rng: rand::Rng>(
+  t              : &'a grammar::T<Texture>,
+  parent         : &Expansion<'a, Texture, Model::Resources>,
+  i              : usize,
+  max_iterations : &mut u32,
+  min_scale      : f32,
+  model          : &Model,
+  report         : &mut Report<Model>,
+  rng            : &mut Rng,
+) -> Option<Expansion<'a, Texture, Model::Resources>> {
+  let params: Vec<f32> =
+    parent.rhs.args.get(i)
+    .map_or(vec!(), |args| args.iter().map(|arg| arg.eval(&parent.params, parent.depth)).collect());
+  let context =
+    grammar::Context {
+      parent : Some(parent.nt),
+      left   : if i == 0 { None } else { Some(parent.rhs.next[i - 1]) },
+      right  : parent.rhs.next.get(i + 1).cloned(),
     };
-  (used_nutrients, mass, t)
-}
-
-pub fn generate<Texture: Clone>(
-  t                   : &grammar::T<Texture>,
-  max_recursion       : u32,
-  mut max_iterations  : u32,
-  min_scale           : f32,
-  available_nutrients : f32,
-  max_mass            : f32,
-) -> T<Texture> {
-  let transform = Matrix::from_value(1.0);
-  vec!(
-    generate_inner(
-      t,
-      grammar::Nonterminal(0),
-      max_recursion,
-      &mut
-      max_iterations,
-      min_scale,
-      transform,
-      available_nutrients,
-      max_mass,
-    ).2
+  expand(
+    t,
+    parent.rhs.next[i],
+    params,
+    parent.depth + 1,
+    context,
+    parent.max_recursion - 1,
+    max_iterations,
+    min_scale,
+    parent.transform,
+    model,
+    model.child(&parent.resources),
+    report,
+    rng,
   )
 }
+
+/// What `DepthFirst::step` did.
+enum Step<'a, Texture: 'a, Resources> {
+  /// Expanded the top expansion's next child, which is now on top.
+  Expanded,
+  /// The top expansion's next child generated nothing.
+  Skipped,
+  /// Popped the top expansion, whose children were all done, and gave it to its parent, which kept it if there
+  /// were enough resources left for it.
+  Finished { kept: bool },
+  /// Popped the start symbol's expansion, whose children were all done.
+  Done(Expansion<'a, Texture, Resources>),
+}
+
+/// Generates depth first from `Nonterminal(0)` one step at a time, keeping the expansions in progress on the heap
+/// so that deep words can't overflow the stack. Each child starts with the transform and resources its parent
+/// left it, after its older siblings have taken theirs.
+struct DepthFirst<'a, Texture: 'a, Model: 'a + ResourceModel> {
+  t          : &'a grammar::T<Texture>,
+  min_scale  : f32,
+  model      : &'a Model,
+  /// Whether to keep finished children's atoms in their parents, rather than leaving them empty.
+  keep_atoms : bool,
+  /// The expansions in progress, from the start symbol's down.
+  stack      : Vec<Expansion<'a, Texture, Model::Resources>>,
+}
+
+impl<'a, Texture: Clone, Model: ResourceModel> DepthFirst<'a, Texture, Model> {
+  fn new<Rng: rand::Rng>(
+    t              : &'a grammar::T<Texture>,
+    max_recursion  : u32,
+    max_iterations : &mut u32,
+    min_scale      : f32,
+    model          : &'a Model,
+    keep_atoms     : bool,
+    report         : &mut Report<Model>,
+    rng            : &mut Rng,
+  ) -> Self {
+    DepthFirst {
+      t          : t,
+      min_scale  : min_scale,
+      model      : model,
+      keep_atoms : keep_atoms,
+      stack      : expand_root(t, max_recursion, max_iterations, min_scale, model, report, rng).into_iter().collect(),
+    }
+  }
+
+  /// `None` once everything's been generated.
+  fn step<Rng: rand::Rng>(
+    &mut self,
+    max_iterations : &mut u32,
+    report         : &mut Report<Model>,
+    rng            : &mut Rng,
+  ) -> Option<Step<'a, Texture, Model::Resources>> {
+    let child = {
+      let parent = self.stack.last()?;
+      let i = parent.atom.subword.len();
+      if i < parent.rhs.next.len() {
+        Some(expand_child(self.t, parent, i, max_iterations, self.min_scale, self.model, report, rng))
+      } else {
+        None
+      }
+    };
+
+    let step =
+      match child {
+        Some(Some(expansion)) => {
+          self.stack.push(expansion);
+          Step::Expanded
+        },
+        Some(None) => {
+          self.stack.last_mut().unwrap().skip_child();
+          Step::Skipped
+        },
+        None => {
+          let mut expansion = self.stack.pop().unwrap();
+          match self.stack.last_mut() {
+            None => Step::Done(expansion),
+            Some(parent) => {
+              let atom = if self.keep_atoms { std::mem::replace(&mut expansion.atom, empty()) } else { empty() };
+              Step::Finished { kept: parent.add_child(self.model, &expansion.resources, atom, report) }
+            },
+          }
+        },
+      };
+    Some(step)
+  }
+}
+
+/// Generate depth first from `Nonterminal(0)`.
+fn generate_depth_first<Texture: Clone, Model: ResourceModel, Rng: rand::Rng>(
+  t              : &grammar::T<Texture>,
+  max_recursion  : u32,
+  max_iterations : &mut u32,
+  min_scale      : f32,
+  model          : &Model,
+  report         : &mut Report<Model>,
+  rng            : &mut Rng,
+) -> Atom<Texture> {
+  let mut generation = DepthFirst::new(t, max_recursion, max_iterations, min_scale, model, true, report, rng);
+  while let Some(step) = generation.step(max_iterations, report, rng) {
+    if let Step::Done(root) = step {
+      report.resources = Some(root.resources);
+      return root.atom
+    }
+  }
+  empty()
+}
+
+/// Generate one level at a time from `Nonterminal(0)`. A level is only expanded if there are enough iterations
+/// left for all of it. Each child starts with the transform and resources its parent left; once a level is done,
+/// children are kept (in order) while what their subtrees used fits in what their older siblings left.
+fn generate_breadth_first<Texture: Clone, Model: ResourceModel, Rng: rand::Rng>(
+  t              : &grammar::T<Texture>,
+  max_recursion  : u32,
+  max_iterations : &mut u32,
+  min_scale      : f32,
+  model          : &Model,
+  report         : &mut Report<Model>,
+  rng            : &mut Rng,
+) -> Atom<Texture> {
+  let root = expand_root(t, max_recursion, max_iterations, min_scale, model, report, rng);
+  // Every expansion so far, level by level, and the indices of its children.
+  let mut expansions = vec!((root, vec!()));
+  let mut level = 0 .. 1;
+  loop {
+    let children: usize =
+      expansions[level.clone()].iter()
+      .filter_map(|(expansion, _)| expansion.as_ref())
+      .map(|expansion| expansion.rhs.next.len())
+      .sum();
+    if children == 0 {
+      break
+    }
+    if children > *max_iterations as usize {
+      // None of the next level is expanded, but some of it would have been too deep anyway.
+      for (expansion, _) in &expansions[level] {
+        if let Some(expansion) = expansion {
+          let cutoff = if expansion.max_recursion == 1 { Cutoff::MaxRecursion } else { Cutoff::MaxIterations };
+          for _ in 0 .. expansion.rhs.next.len() {
+            report.cut(cutoff);
+          }
+        }
+      }
+      break
+    }
+
+    let next_level = expansions.len() .. expansions.len() + children;
+    for i in level {
+      let count = expansions[i].0.as_ref().map_or(0, |expansion| expansion.rhs.next.len());
+      for j in 0 .. count {
+        let child =
+          expand_child(t, expansions[i].0.as_ref().unwrap(), j, max_iterations, min_scale, model, report, rng);
+        let index = expansions.len();
+        expansions[i].1.push(index);
+        expansions.push((child, vec!()));
+      }
+    }
+    level = next_level;
+  }
+
+  // Children come after their parents, so going backwards finishes them first. Expansions that generated nothing
+  // are never finished.
+  let mut finished: Vec<Option<(Model::Resources, Atom<Texture>)>> = expansions.iter().map(|_| None).collect();
+  for (i, (expansion, children)) in expansions.into_iter().enumerate().rev() {
+    if let Some(mut expansion) = expansion {
+      for child in children {
+        match finished[child].take() {
+          None => expansion.skip_child(),
+          Some((resources, atom)) => {
+            expansion.add_child(model, &resources, atom, report);
+          },
+        }
+      }
+      // The children of the last level weren't expanded.
+      while expansion.atom.subword.len() < expansion.rhs.next.len() {
+        expansion.skip_child();
+      }
+      finished[i] = Some((expansion.resources, expansion.atom));
+    }
+  }
+  match finished[0].take() {
+    None => empty(),
+    Some((resources, atom)) => {
+      report.resources = Some(resources);
+      atom
+    },
+  }
+}
+
+/// The order `generate_in_order` expands nonterminals in, which decides what's cut when `max_iterations` runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+  /// Each child's whole subtree before its next sibling, so the last subtrees are the ones left empty.
+  DepthFirst,
+  /// One level at a time, so the deepest levels are cut evenly across the whole word. Younger siblings don't
+  /// have to leave resources for their older siblings' subtrees, but are still dropped if there aren't enough left
+  /// for their own.
+  BreadthFirst,
+}
+
+/// Expand a word from `Nonterminal(0)`, with `model` limiting how much it can grow (e.g.
+/// `resources::NutrientsAndMass`). Alternative rules are picked using `rng`, so seeding it the same way reproduces
+/// the same word. Fails if the grammar has any fatal errors (see `grammar::T::check`).
+pub fn generate<Texture: Clone, Model: ResourceModel, Rng: rand::Rng>(
+  t              : &grammar::T<Texture>,
+  max_recursion  : u32,
+  max_iterations : u32,
+  min_scale      : f32,
+  model          : &Model,
+  rng            : &mut Rng,
+) -> Result<T<Texture>, Vec<grammar::Error>> {
+  generate_in_order(t, Order::DepthFirst, max_recursion, max_iterations, min_scale, model, rng)
+}
+
+/// Like `generate`, but expanding in the given order.
+pub fn generate_in_order<Texture: Clone, Model: ResourceModel, Rng: rand::Rng>(
+  t              : &grammar::T<Texture>,
+  order          : Order,
+  max_recursion  : u32,
+  max_iterations : u32,
+  min_scale      : f32,
+  model          : &Model,
+  rng            : &mut Rng,
+) -> Result<T<Texture>, Vec<grammar::Error>> {
+  generate_with_report(t, order, max_recursion, max_iterations, min_scale, model, rng).map(|(word, _)| word)
+}
+
+/// Like `generate_in_order`, but also reporting where and why the word was cut off.
+pub fn generate_with_report<Texture: Clone, Model: ResourceModel, Rng: rand::Rng>(
+  t                  : &grammar::T<Texture>,
+  order              : Order,
+  max_recursion      : u32,
+  mut max_iterations : u32,
+  min_scale          : f32,
+  model              : &Model,
+  rng                : &mut Rng,
+) -> Result<(T<Texture>, Report<Model>), Vec<grammar::Error>> {
+  t.check()?;
+
+  let generate =
+    match order {
+      Order::DepthFirst => generate_depth_first,
+      Order::BreadthFirst => generate_breadth_first,
+    };
+  let mut report = Report::new();
+  let atom = generate(t, max_recursion, &mut max_iterations, min_scale, model, &mut report, rng);
+  Ok((vec!(atom), report))
+}
+
+/// A branch or polygon from `branches`, placed where `render_mesh` would draw it.
+#[derive(Debug, Clone)]
+pub enum Placed<Texture> {
+  Branch {
+    texture_id : Texture,
+    /// The transform at the start of the branch, which grows along its y axis.
+    transform  : Matrix4,
+    width      : f32,
+    length     : f32,
+    taper      : f32,
+    bend       : f32,
+  },
+  Polygon {
+    texture_id : Texture,
+    transform  : Matrix4,
+    points     : Vec<[f32; 2]>,
+  },
+}
+
+/// The most dropped children `branches` keeps track of at once.
+const MAX_DROPPED: usize = 1 << 16;
+
+/// Which children of a word are dropped, found by generating it ahead of `branches`.
+struct Dropped<'a, Texture: 'a, Model: 'a, Rng> {
+  t              : &'a grammar::T<Texture>,
+  max_recursion  : u32,
+  max_iterations : u32,
+  min_scale      : f32,
+  model          : &'a Model,
+  /// As it was before generating anything.
+  rng            : Rng,
+  /// The depth first indices of the dropped children that aren't inside other dropped ones, in order, with the
+  /// start symbol's at 0.
+  indices        : std::collections::VecDeque<u32>,
+  /// The index from which on `indices` isn't complete, if there were too many dropped children to keep.
+  until          : Option<u32>,
+}
+
+impl<'a, Texture: Clone, Model: ResourceModel, Rng: rand::Rng + Clone> Dropped<'a, Texture, Model, Rng> {
+  /// Generate the word again, to find the dropped children from index `from` on.
+  fn find(&mut self, from: u32, report: &mut Report<Model>) {
+    self.indices.clear();
+    self.until = None;
+    let mut max_iterations = self.max_iterations;
+    let mut rng = self.rng.clone();
+    let mut generation =
+      DepthFirst::new(
+        self.t, self.max_recursion, &mut max_iterations, self.min_scale, self.model, false, report, &mut rng,
+      );
+    // The indices of the expansions in progress below the start symbol's.
+    let mut in_progress = vec!();
+    let mut next_index = 1;
+    while let Some(step) = generation.step(&mut max_iterations, report, &mut rng) {
+      match step {
+        Step::Expanded => {
+          in_progress.push(next_index);
+          next_index += 1;
+        },
+        Step::Skipped => {},
+        Step::Finished { kept } => {
+          let index = in_progress.pop().unwrap();
+          if !kept && index >= from && self.until.map_or(true, |until| index < until) {
+            // Any dropped children with higher indices are inside this one, so dropped with it.
+            while self.indices.back().map_or(false, |&last| last > index) {
+              self.indices.pop_back();
+            }
+            self.indices.push_back(index);
+            if self.indices.len() > MAX_DROPPED {
+              self.until = self.indices.pop_back();
+            }
+          }
+        },
+        Step::Done(root) => report.resources = Some(root.resources),
+      }
+    }
+  }
+
+  /// Whether the child with depth first index `index` is dropped (or inside a dropped one), asked in order.
+  fn contains(&mut self, index: u32) -> bool {
+    if self.until.map_or(false, |until| index >= until) {
+      self.find(index, &mut Report::new());
+    }
+    if self.indices.front() == Some(&index) {
+      self.indices.pop_front();
+      true
+    } else {
+      false
+    }
+  }
+}
+
+/// The branches and polygons of a word, generated as they're needed (see `branches`).
+pub struct Branches<'a, Texture: 'a, Model: 'a + ResourceModel, Rng> {
+  generation     : DepthFirst<'a, Texture, Model>,
+  max_iterations : u32,
+  rng            : Rng,
+  report         : Report<Model>,
+  /// What generating the word again reports, which is the same as `report` once it's done.
+  replay_report  : Report<Model>,
+  dropped        : Dropped<'a, Texture, Model, Rng>,
+  next_index     : u32,
+  /// For each expansion in progress, the transform its actions left, or `None` if it was dropped.
+  transforms     : Vec<Option<Matrix4>>,
+  placed         : std::collections::VecDeque<Placed<Texture>>,
+}
+
+/// Like `generate` (with the same `rng`), but rather than building the whole word, place its branches and polygons
+/// as they're iterated over, in the order `render_mesh` draws them.
+///
+/// Whether a child is dropped is only known once all of its descendants are done, so the word is generated twice:
+/// first just to find out which children are dropped, and then again (from a copy of `rng`) as it's iterated over.
+/// That takes about twice as long as `generate`. Besides the expansions in progress, an index is kept for up to
+/// 65536 dropped children at a time; if `model` drops more than that, the word is generated again to find the rest
+/// once they're needed. So memory use depends on how deep the word is, rather than how big it is.
+pub fn branches<'a, Texture: Clone, Model: ResourceModel, Rng: rand::Rng + Clone>(
+  t              : &'a grammar::T<Texture>,
+  max_recursion  : u32,
+  max_iterations : u32,
+  min_scale      : f32,
+  model          : &'a Model,
+  rng            : Rng,
+) -> Result<Branches<'a, Texture, Model, Rng>, Vec<grammar::Error>> {
+  t.check()?;
+
+  let mut report = Report::new();
+  let mut dropped =
+    Dropped {
+      t              : t,
+      max_recursion  : max_recursion,
+      max_iterations : max_iterations,
+      min_scale      : min_scale,
+      model          : model,
+      rng            : rng.clone(),
+      indices        : std::collections::VecDeque::new(),
+      until          : None,
+    };
+  dropped.find(1, &mut report);
+
+  let mut max_iterations = max_iterations;
+  let mut rng = rng;
+  let mut replay_report = Report::new();
+  let mut generation =
+    DepthFirst::new(t, max_recursion, &mut max_iterations, min_scale, model, false, &mut replay_report, &mut rng);
+  let mut placed = std::collections::VecDeque::new();
+  let transforms =
+    generation.stack.iter_mut()
+    .map(|root| {
+      let actions = std::mem::take(&mut root.atom.actions);
+      Some(render::place(actions, Matrix4::from_value(1.0), &mut placed))
+    })
+    .collect();
+  Ok(Branches {
+    generation     : generation,
+    max_iterations : max_iterations,
+    rng            : rng,
+    report         : report,
+    replay_report  : replay_report,
+    dropped        : dropped,
+    next_index     : 1,
+    transforms     : transforms,
+    placed         : placed,
+  })
+}
+
+impl<'a, Texture, Model: ResourceModel, Rng> Branches<'a, Texture, Model, Rng> {
+  /// Where and why the word was cut off.
+  pub fn report(&self) -> &Report<Model> {
+    &self.report
+  }
+}
+
+impl<'a, Texture: Clone, Model: ResourceModel, Rng: rand::Rng + Clone> Iterator
+  for Branches<'a, Texture, Model, Rng>
+{
+  type Item = Placed<Texture>;
+
+  fn next(&mut self) -> Option<Placed<Texture>> {
+    loop {
+      if let Some(placed) = self.placed.pop_front() {
+        return Some(placed)
+      }
+
+      match self.generation.step(&mut self.max_iterations, &mut self.replay_report, &mut self.rng)? {
+        Step::Expanded => {
+          let index = self.next_index;
+          self.next_index += 1;
+          let dropped = self.dropped.contains(index);
+          // Children of dropped expansions are dropped too.
+          let transform =
+            match *self.transforms.last().unwrap() {
+              Some(transform) if !dropped => {
+                let expansion = self.generation.stack.last_mut().unwrap();
+                let actions = std::mem::take(&mut expansion.atom.actions);
+                Some(render::place(actions, transform, &mut self.placed))
+              },
+              _ => None,
+            };
+          self.transforms.push(transform);
+        },
+        Step::Skipped => {},
+        Step::Finished { .. } | Step::Done(_) => {
+          self.transforms.pop();
+        },
+      }
+    }
+  }
+}
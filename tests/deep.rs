@@ -0,0 +1,79 @@
+extern crate lsystems;
+extern crate rand;
+
+use lsystems::*;
+
+/// Deeper than a recursive traversal could go on a small stack.
+const DEPTH: u32 = 30000;
+
+const RESOURCES: resources::NutrientsAndMass =
+  resources::NutrientsAndMass { available_nutrients: 1000.0, max_mass: 1000.0 };
+
+/// Run `f` on a thread with a small stack, like a worker thread's.
+fn with_small_stack<F: FnOnce() + Send + 'static>(f: F) {
+  std::thread::Builder::new().stack_size(256 * 1024).spawn(f).unwrap().join().unwrap();
+}
+
+/// A stem of short branches, each nested in the one before.
+const DEEP: &'static str = "S -> branch(Stem, 1, 0.001) rotate(0.01) S\n";
+
+/// `DEEP`, `DEPTH` branches deep.
+fn deep_word() -> word::T<String> {
+  let t = text::parse::<String>(DEEP).unwrap();
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  word::generate(&t, DEPTH, 2 * DEPTH, 0.0, &RESOURCES, &mut rng).unwrap()
+}
+
+fn depth(word: &word::T<String>) -> u32 {
+  let mut depth = 0;
+  let mut atoms = word;
+  while let Some(atom) = atoms.first() {
+    depth += 1;
+    atoms = &atom.subword;
+  }
+  depth
+}
+
+#[test]
+fn generate_deep() {
+  with_small_stack(|| {
+    // The innermost expansion stops at the recursion limit, and so is empty.
+    assert_eq!(depth(&deep_word()), DEPTH + 1);
+  });
+}
+
+#[test]
+fn limits_deep() {
+  with_small_stack(|| {
+    let t = text::parse::<String>("S -> branch(Stem, 1, 0.001) S\n").unwrap();
+    let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+    let word = word::generate(&t, DEPTH / 2, 2 * DEPTH, 0.0, &RESOURCES, &mut rng).unwrap();
+    assert_eq!(depth(&word), DEPTH / 2 + 1);
+    let word = word::generate(&t, DEPTH, DEPTH / 3, 0.0, &RESOURCES, &mut rng).unwrap();
+    assert_eq!(depth(&word), DEPTH / 3 + 1);
+  });
+}
+
+#[test]
+fn render_deep() {
+  with_small_stack(|| {
+    let word = deep_word();
+    let vertices = render(&word).to_hashmap();
+    assert_eq!(vertices["Stem"].len(), 6 * DEPTH as usize);
+    let options =
+      RenderOptions { joints: JointStyle::Round { segments: 8 }, continuous: true, .. Default::default() };
+    render_with(&word, &options);
+    let (vertices, _) = render_mesh(&word, &Default::default()).to_hashmap().remove("Stem").unwrap();
+    assert_eq!(vertices.len(), 2 * 9 * DEPTH as usize);
+  });
+}
+
+#[test]
+fn branches_deep() {
+  with_small_stack(|| {
+    let t = text::parse::<String>(DEEP).unwrap();
+    let rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+    let branches = word::branches(&t, DEPTH, 2 * DEPTH, 0.0, &RESOURCES, rng).unwrap();
+    assert_eq!(branches.count(), DEPTH as usize);
+  });
+}
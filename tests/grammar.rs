@@ -0,0 +1,130 @@
+extern crate lsystems;
+
+use lsystems::*;
+use lsystems::alphabet::Terminal;
+use lsystems::expr::Expr;
+use lsystems::grammar::{Error, Nonterminal};
+
+fn parse(source: &str) -> grammar::T<String> {
+  text::parse::<String>(source).unwrap()
+}
+
+#[test]
+fn valid() {
+  let t = parse("S -> branch(Stem, 1, 1) push rotate(10) pop A\nA -> polygon(Leaf, 0, 0, 1, 0, 0, 1)\n");
+  assert_eq!(t.validate(), Ok(()));
+  assert_eq!(t.check(), Ok(()));
+}
+
+#[test]
+fn empty() {
+  let t: grammar::T<String> = grammar::T { rules: vec!() };
+  assert_eq!(t.validate(), Err(vec!(Error::Empty)));
+  assert_eq!(t.check(), Err(vec!(Error::Empty)));
+}
+
+#[test]
+fn undefined_nonterminal() {
+  let mut t = parse("S -> branch(Stem, 1, 1) A\nA -> rotate(10)\n");
+  t.rules[1][0].next.push(Nonterminal(5));
+  let errors =
+    vec!(Error::UndefinedNonterminal { rule: Nonterminal(1), alternative: 0, nonterminal: Nonterminal(5) });
+  assert_eq!(t.validate(), Err(errors.clone()));
+  assert_eq!(t.check(), Err(errors));
+}
+
+#[test]
+fn unreachable() {
+  let t = parse("S -> branch(Stem, 1, 1)\nA -> rotate(10) S\n");
+  assert_eq!(t.validate(), Err(vec!(Error::Unreachable(Nonterminal(1)))));
+  assert_eq!(t.check(), Ok(()));
+}
+
+#[test]
+fn invalid_weight() {
+  let mut t = parse("S -> branch(Stem, 1, 1)\nS -> rotate(10)\n");
+  t.rules[0][0].weight = -1.0;
+  t.rules[0][1].weight = std::f32::NAN;
+  let errors =
+    vec!(
+      Error::InvalidWeight { rule: Nonterminal(0), alternative: 0 },
+      Error::InvalidWeight { rule: Nonterminal(0), alternative: 1 },
+    );
+  assert_eq!(t.validate(), Err(errors));
+  assert_eq!(t.check(), Ok(()));
+}
+
+#[test]
+fn non_finite_transform() {
+  let mut t = parse("S -> rotate(10) branch(Stem, 1, 1) move(1)\n");
+  for action in &mut t.rules[0][0].actions {
+    match action {
+      &mut Terminal::Transform(ref mut transform) => transform.rotation = Expr::Const(std::f32::INFINITY),
+      &mut Terminal::AddBranch { ref mut bend, .. } => *bend = Expr::Const(std::f32::NAN),
+      &mut Terminal::Move { ref mut length } => *length = Expr::Const(std::f32::NEG_INFINITY),
+      _ => {},
+    }
+  }
+  let errors =
+    (0 .. 3)
+    .map(|action| Error::NonFiniteTransform { rule: Nonterminal(0), alternative: 0, action: action })
+    .collect();
+  assert_eq!(t.validate(), Err(errors));
+  assert_eq!(t.check(), Ok(()));
+}
+
+#[test]
+fn negative_branch() {
+  let t = parse("S -> branch(Stem, 1, 1) branch(Stem, -0.1, 1) branch(Stem, 1, 1, -1)\n");
+  let errors =
+    vec!(
+      Error::NegativeBranch { rule: Nonterminal(0), alternative: 0, action: 1 },
+      Error::NegativeBranch { rule: Nonterminal(0), alternative: 0, action: 2 },
+    );
+  assert_eq!(t.validate(), Err(errors));
+  assert_eq!(t.check(), Ok(()));
+}
+
+#[test]
+fn invalid_polygon() {
+  let mut t = parse("S -> polygon(Leaf, 0, 0, 1, 0, 0, 1) polygon(Leaf, 0, 0, 1, 0, 0, 1)\n");
+  match &mut t.rules[0][0].actions[0] {
+    &mut Terminal::Polygon { ref mut points, .. } => { points.pop(); },
+    _ => panic!("not a polygon"),
+  }
+  match &mut t.rules[0][0].actions[1] {
+    &mut Terminal::Polygon { ref mut points, .. } => points[1][0] = Expr::Const(std::f32::NAN),
+    _ => panic!("not a polygon"),
+  }
+  let errors =
+    vec!(
+      Error::InvalidPolygon { rule: Nonterminal(0), alternative: 0, action: 0 },
+      Error::InvalidPolygon { rule: Nonterminal(0), alternative: 0, action: 1 },
+    );
+  assert_eq!(t.validate(), Err(errors));
+  assert_eq!(t.check(), Ok(()));
+}
+
+#[test]
+fn unmatched_pop() {
+  let t = parse("S -> pop push branch(Stem, 1, 1) pop pop\n");
+  let errors =
+    vec!(
+      Error::UnmatchedPop { rule: Nonterminal(0), alternative: 0, action: 0 },
+      Error::UnmatchedPop { rule: Nonterminal(0), alternative: 0, action: 4 },
+    );
+  assert_eq!(t.validate(), Err(errors));
+  assert_eq!(t.check(), Ok(()));
+}
+
+#[test]
+fn check_only_reports_fatal_errors() {
+  let mut t = parse("S -> pop branch(Stem, -1, 1) A\nA -> rotate(10)\nB -> S\n");
+  t.rules[1][0].next.push(Nonterminal(5));
+  t.rules[1][0].weight = -1.0;
+  let errors = t.validate().unwrap_err();
+  assert_eq!(errors.len(), 5);
+  let fatal = vec!(Error::UndefinedNonterminal { rule: Nonterminal(1), alternative: 0, nonterminal: Nonterminal(5) });
+  assert_eq!(errors.iter().filter(|e| e.is_fatal()).cloned().collect::<Vec<Error>>(), fatal);
+  assert_eq!(t.check(), Err(fatal));
+}
@@ -0,0 +1,40 @@
+extern crate lsystems;
+
+use lsystems::*;
+
+/// Two triangles covering the square from (0, 0) to (1, 1).
+fn square() -> Vec<vertex::T> {
+  [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [0.0, 1.0]].iter()
+    .map(|&p| vertex::T { screen_posn: p, texture_posn: [0.0, 0.0] })
+    .collect()
+}
+
+#[test]
+fn textures_drawn_in_order() {
+  // Every texture covers the whole image, so only the last one drawn shows.
+  let vertices: std::collections::HashMap<u8, Vec<vertex::T>> = (0 .. 8).map(|id| (id, square())).collect();
+  let mut image = raster::new(4, 4, [255, 255, 255, 255]);
+  image.draw(&vertices, |&id, _| [id * 30, 0, 255 - id * 30, 255]);
+  for pixel in image.pixels.chunks(4) {
+    assert_eq!(pixel, &[210, 0, 45, 255]);
+  }
+}
+
+#[test]
+fn overlapping_textures() {
+  // The right half of the image is covered by both textures.
+  let stretched = |scale: f32, offset: f32| -> Vec<vertex::T> {
+    square().into_iter()
+      .map(|v| vertex::T { screen_posn: [scale * v.screen_posn[0] + offset, v.screen_posn[1]], .. v })
+      .collect()
+  };
+  let mut vertices = std::collections::HashMap::new();
+  vertices.insert(1, stretched(1.0, 1.0));
+  vertices.insert(0, stretched(2.0, 0.0));
+  let mut image = raster::new(4, 2, [0, 0, 0, 0]);
+  image.draw(&vertices, |&id, _| if id == 0 { [255, 0, 0, 255] } else { [0, 0, 255, 128] });
+  for row in image.pixels.chunks(4 * 4) {
+    let pixels: Vec<&[u8]> = row.chunks(4).collect();
+    assert_eq!(pixels, vec!(&[255, 0, 0, 255][..], &[255, 0, 0, 255], &[127, 0, 128, 255], &[127, 0, 128, 255]));
+  }
+}
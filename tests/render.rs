@@ -0,0 +1,30 @@
+extern crate lsystems;
+extern crate rand;
+
+use lsystems::*;
+
+/// How many triangles a single branch bent by `bend` degrees is drawn as, in 2D and in 3D.
+fn triangles(bend: f32, options: &RenderOptions) -> (usize, usize) {
+  let t = text::parse::<String>(&format!("S -> branch(Stem, 0.1, 1, 1, {})\n", bend)).unwrap();
+  let resources = resources::NutrientsAndMass { available_nutrients: 1000.0, max_mass: 1000.0 };
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  let word = word::generate(&t, 10, 10, 0.0, &resources, &mut rng).unwrap();
+  let vertices = render_with(&word, options).to_hashmap();
+  let mesh = render_mesh(&word, options).to_hashmap();
+  (vertices["Stem"].len() / 3, mesh["Stem"].1.len() / 3)
+}
+
+#[test]
+fn curve_segments() {
+  let default = Default::default();
+  // One quad for every 5 degrees, each two triangles, or two per side of the prism in 3D.
+  assert_eq!(triangles(0.0, &default), (2, 16));
+  assert_eq!(triangles(12.0, &default), (6, 48));
+  assert_eq!(triangles(-12.0, &default), (6, 48));
+  // No more than a full turn's worth.
+  assert_eq!(triangles(360.0, &default), (144, 1152));
+  assert_eq!(triangles(36000.0, &default), (144, 1152));
+
+  let options = RenderOptions { curve_segments: 100, .. Default::default() };
+  assert_eq!(triangles(36000.0, &options), (200, 1600));
+}
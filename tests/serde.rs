@@ -0,0 +1,73 @@
+#![cfg(feature = "serde")]
+
+extern crate lsystems;
+extern crate rand;
+extern crate ron;
+extern crate serde_json;
+
+use lsystems::*;
+
+const GRAMMAR: &'static str = include_str!("../examples/grammars/parametric_tree.txt");
+
+fn generate(t: &grammar::T<String>) -> word::T<String> {
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  let resources = resources::NutrientsAndMass { available_nutrients: 1000.0, max_mass: 1000.0 };
+  word::generate(t, 8, 1000, 0.0, &resources, &mut rng).unwrap()
+}
+
+fn positions(vertices: vertices::T<String>) -> Vec<(String, Vec<[f32; 2]>)> {
+  let mut positions: Vec<_> =
+    vertices.to_hashmap().into_iter()
+    .map(|(texture, vertices)| (texture, vertices.iter().map(|v| v.screen_posn).collect()))
+    .collect();
+  positions.sort_by(|a, b| a.0.cmp(&b.0));
+  positions
+}
+
+#[test]
+fn grammar_json() {
+  let t = text::parse::<String>(GRAMMAR).unwrap();
+  let loaded: grammar::T<String> = serde_json::from_str(&serde_json::to_string(&t).unwrap()).unwrap();
+  assert_eq!(text::print(&loaded), text::print(&t));
+  assert_eq!(positions(render(&generate(&loaded))), positions(render(&generate(&t))));
+}
+
+#[test]
+fn grammar_ron() {
+  let t = text::parse::<String>(GRAMMAR).unwrap();
+  let loaded: grammar::T<String> = ron::from_str(&ron::to_string(&t).unwrap()).unwrap();
+  assert_eq!(text::print(&loaded), text::print(&t));
+  assert_eq!(positions(render(&generate(&loaded))), positions(render(&generate(&t))));
+}
+
+#[test]
+fn word_json() {
+  let word = generate(&text::parse::<String>(GRAMMAR).unwrap());
+  let saved = serde_json::to_string(&word).unwrap();
+  let loaded: word::T<String> = serde_json::from_str(&saved).unwrap();
+  assert_eq!(serde_json::to_string(&loaded).unwrap(), saved);
+  assert_eq!(positions(render(&loaded)), positions(render(&word)));
+}
+
+#[test]
+fn word_ron() {
+  let word = generate(&text::parse::<String>(GRAMMAR).unwrap());
+  let saved = ron::to_string(&word).unwrap();
+  let loaded: word::T<String> = ron::from_str(&saved).unwrap();
+  assert_eq!(ron::to_string(&loaded).unwrap(), saved);
+  assert_eq!(positions(render(&loaded)), positions(render(&word)));
+}
+
+#[test]
+fn vertices_json() {
+  let word = generate(&text::parse::<String>(GRAMMAR).unwrap());
+  let loaded: vertices::T<String> = serde_json::from_str(&serde_json::to_string(&render(&word)).unwrap()).unwrap();
+  assert_eq!(positions(loaded), positions(render(&word)));
+}
+
+#[test]
+fn vertices_ron() {
+  let word = generate(&text::parse::<String>(GRAMMAR).unwrap());
+  let loaded: vertices::T<String> = ron::from_str(&ron::to_string(&render(&word)).unwrap()).unwrap();
+  assert_eq!(positions(loaded), positions(render(&word)));
+}
@@ -0,0 +1,119 @@
+extern crate lsystems;
+extern crate rand;
+
+use lsystems::*;
+
+type Vertices = std::collections::HashMap<u32, Vec<vertex::T>>;
+
+/// Never runs short, so that rounding in folded transforms can't change where generation is cut off.
+struct Unlimited;
+
+impl resources::ResourceModel for Unlimited {
+  type Resources = ();
+  type Shortage = ();
+
+  fn initial(&self) {}
+  fn child(&self, _: &()) {}
+  fn branch(&self, _: &mut (), _: f32, _: f32, _: f32) -> Result<(), ()> { Ok(()) }
+  fn add_child(&self, _: &mut (), _: &()) -> Result<(), ()> { Ok(()) }
+  fn pop(&self, _: &mut (), _: &()) {}
+}
+
+/// Render `t`, with the same randomness and budgets every time.
+fn render_grammar<Model: resources::ResourceModel>(t: &grammar::T<u32>, min_scale: f32, model: &Model) -> Vertices {
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([5, 6, 7, 8]);
+  let word = word::generate(t, 1 << 5, 1 << 12, min_scale, model, &mut rng).unwrap();
+  render(&word).to_hashmap()
+}
+
+/// Render `t` the way the examples do, so resources and `min_scale` cut it off.
+fn render_limited(t: &grammar::T<u32>) -> Vertices {
+  let resources = resources::NutrientsAndMass { available_nutrients: 1000000.0, max_mass: 1000000.0 };
+  render_grammar(t, 0.01, &resources)
+}
+
+/// Render `t` so that only the recursion and iteration limits cut it off.
+fn render_unlimited(t: &grammar::T<u32>) -> Vertices {
+  render_grammar(t, 0.0, &Unlimited)
+}
+
+/// Check that simplifying `t` with `options` doesn't change how `render` renders it, up to a relative `tolerance`.
+fn assert_renders_the_same(
+  t         : &grammar::T<u32>,
+  options   : &grammar::SimplifyOptions,
+  render    : fn(&grammar::T<u32>) -> Vertices,
+  tolerance : f32,
+) {
+  let before = render(t);
+  let mut simplified = t.clone();
+  grammar::simplify_with(&mut simplified, options).unwrap();
+  let after = render(&simplified);
+
+  let printed = || format!("{}\nsimplified to\n{}", text::print(t), text::print(&simplified));
+  let mut textures: Vec<&u32> = before.keys().collect();
+  textures.sort();
+  let mut after_textures: Vec<&u32> = after.keys().collect();
+  after_textures.sort();
+  assert_eq!(textures, after_textures, "{}", printed());
+  for texture in textures {
+    let (before, after) = (&before[texture], &after[texture]);
+    assert_eq!(before.len(), after.len(), "{}", printed());
+    for (before, after) in before.iter().zip(after.iter()) {
+      for i in 0 .. 2 {
+        let (before, after) = (before.screen_posn[i], after.screen_posn[i]);
+        assert!(
+          (before - after).abs() <= tolerance * before.abs().max(1.0),
+          "{} != {}\n{}", before, after, printed(),
+        );
+      }
+    }
+  }
+}
+
+/// Mutate the fractal plant a couple of hundred times, checking each mutant.
+fn for_mutants<F: FnMut(&grammar::T<u32>)>(mut check: F) {
+  let source = include_str!("../examples/grammars/fractal_plant.txt").replace("Stem", "0");
+  for seed in 1 .. 11 {
+    let mut t = text::parse::<u32>(&source).unwrap();
+    let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([seed, 2, 3, 4]);
+    for _ in 0 .. 20 {
+      mutate(&mut t, &mut rng).unwrap();
+      check(&t);
+    }
+  }
+}
+
+#[test]
+fn mutated_render_exactly_the_same() {
+  for_mutants(|t| assert_renders_the_same(t, &Default::default(), render_limited, 0.0));
+}
+
+#[test]
+fn mutated_render_nearly_the_same_folded() {
+  let options = grammar::SimplifyOptions { fold_transforms: true, .. Default::default() };
+  for_mutants(|t| assert_renders_the_same(t, &options, render_unlimited, 1e-3));
+}
+
+#[test]
+fn folding_is_opt_in() {
+  let source = "S -> rotate(10) rotate(0) rotate(20) branch(Stem, 0.2, 1) S\n";
+  let mut t = text::parse::<String>(source).unwrap();
+  grammar::simplify(&mut t).unwrap();
+  assert_eq!(t.rules[0][0].actions.len(), 3);
+
+  let options = grammar::SimplifyOptions { fold_transforms: true, .. Default::default() };
+  grammar::simplify_with(&mut t, &options).unwrap();
+  assert_eq!(t.rules[0][0].actions.len(), 2);
+}
+
+#[test]
+fn inlining_is_opt_in() {
+  let source = "S -> branch(Stem, 0.2, 1) A A\nA -> B\nB -> rotate(30) S\nC -> S\n";
+  let mut t = text::parse::<String>(source).unwrap();
+  grammar::simplify(&mut t).unwrap();
+  assert_eq!(text::print(&t), text::print(&text::parse::<String>(&source.replace("C -> S\n", "")).unwrap()));
+
+  let options = grammar::SimplifyOptions { inline_rules: true, .. Default::default() };
+  grammar::simplify_with(&mut t, &options).unwrap();
+  assert_eq!(t.rules.len(), 2);
+}
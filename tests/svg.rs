@@ -0,0 +1,78 @@
+extern crate lsystems;
+extern crate rand;
+
+use lsystems::*;
+
+/// The signed area of every triangle in an SVG document written by `svg::write`.
+fn areas(svg: &str) -> Vec<f32> {
+  svg.split(" d=\"").skip(1)
+    .flat_map(|path| path.split('"').next().unwrap().split('Z'))
+    .filter(|triangle| !triangle.trim().is_empty())
+    .map(|triangle| {
+      let p: Vec<(f32, f32)> =
+        triangle.split_whitespace()
+        .map(|point| {
+          let mut xy = point[1 ..].split(',').map(|x| x.parse::<f32>().unwrap());
+          (xy.next().unwrap(), xy.next().unwrap())
+        })
+        .collect();
+      assert_eq!(p.len(), 3);
+      (p[1].0 - p[0].0) * (p[2].1 - p[0].1) - (p[1].1 - p[0].1) * (p[2].0 - p[0].0)
+    })
+    .collect()
+}
+
+#[test]
+fn consistent_winding() {
+  // Round joints and polygons are wound the other way from branches, and mirroring flips everything.
+  let source = "
+    S -> branch(Stem, 0.2, 1) rotate(30) branch(Stem, 0.2, 1) polygon(Stem, 0, 0, 0.2, 0, 0, 0.2) L L
+    L -> scale(-0.5, 0.5) rotate(20) S
+  ";
+  let t = text::parse::<String>(source).unwrap();
+  let resources = resources::NutrientsAndMass { available_nutrients: 1000000.0, max_mass: 1000000.0 };
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  let word = word::generate(&t, 8, 1 << 10, 0.0, &resources, &mut rng).unwrap();
+  let options = RenderOptions { joints: JointStyle::Round { segments: 8 }, .. Default::default() };
+  let shapes = render_with(&word, &options).to_shapes();
+
+  let mut svg = vec!();
+  svg::write(&shapes, |_| "fill:#336619".to_string(), &mut svg).unwrap();
+  let areas = areas(&String::from_utf8(svg).unwrap());
+  assert!(areas.len() > 100);
+  assert!(areas.iter().all(|&area| area >= 0.0), "{:?}", areas);
+}
+
+#[test]
+fn path_per_branch() {
+  let source = "S -> branch(Stem, 0.2, 1) rotate(30) branch(Stem, 0.2, 1, 1, 40) polygon(Leaf, 0, 0, 0.2, 0, 0, 0.2)";
+  let t = text::parse::<String>(source).unwrap();
+  let resources = resources::NutrientsAndMass { available_nutrients: 1000000.0, max_mass: 1000000.0 };
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  let word = word::generate(&t, 8, 1 << 10, 0.0, &resources, &mut rng).unwrap();
+  let shapes = render(&word).to_shapes();
+  let indexed = render_indexed(&word).to_shapes();
+  for (texture_id, shapes) in &shapes {
+    let lengths = |shapes: &Vec<Vec<vertex::T>>| -> Vec<usize> { shapes.iter().map(|s| s.len()).collect() };
+    assert_eq!(lengths(shapes), lengths(&indexed[texture_id]));
+  }
+
+  let mut svg = vec!();
+  let style = |texture_id: &String| if texture_id == "Stem" { "fill:#336619" } else { "fill:#66cc33" }.to_string();
+  svg::write(&shapes, style, &mut svg).unwrap();
+  let svg = String::from_utf8(svg).unwrap();
+
+  // The groups are in order of their styles, and the bent branch is made of several quads.
+  let groups: Vec<&str> = svg.split("<g ").skip(1).collect();
+  assert_eq!(groups.len(), 2, "{}", svg);
+  assert!(groups[0].starts_with("style=\"fill:#336619\""), "{}", svg);
+  assert!(groups[1].starts_with("style=\"fill:#66cc33\""), "{}", svg);
+  let triangles = |group: &str| -> Vec<usize> {
+    group.split("<path").skip(1).map(|path| areas(path).len()).collect()
+  };
+  let stems = triangles(groups[0]);
+  assert_eq!(stems.len(), 2);
+  assert_eq!(stems[0], 2);
+  assert!(stems[1] > 2);
+  assert_eq!(triangles(groups[1]), vec!(1));
+}
@@ -0,0 +1,140 @@
+extern crate lsystems;
+extern crate rand;
+
+use lsystems::*;
+
+/// The text of every grammar in `examples/grammars`, with its file name.
+fn example_grammars() -> Vec<(String, String)> {
+  let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/grammars");
+  let mut grammars: Vec<(String, String)> =
+    std::fs::read_dir(dir).unwrap()
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().map_or(false, |extension| extension == "txt"))
+    .map(|path| (path.display().to_string(), std::fs::read_to_string(&path).unwrap()))
+    .collect();
+  grammars.sort();
+  assert!(!grammars.is_empty());
+  grammars
+}
+
+/// Check that printing `t` and parsing it again gives back exactly the same grammar, and so prints the same.
+fn assert_round_trips<Texture>(t: &grammar::T<Texture>) where
+  Texture : std::fmt::Debug + std::fmt::Display + std::str::FromStr,
+{
+  let printed = text::print(t);
+  let parsed = text::parse::<Texture>(&printed).unwrap_or_else(|e| panic!("{}\n{}", e, printed));
+  assert_eq!(format!("{:?}", parsed), format!("{:?}", t), "\n{}", printed);
+  assert_eq!(text::print(&parsed), printed);
+}
+
+#[test]
+fn examples_round_trip() {
+  for (name, source) in example_grammars() {
+    let t = text::parse::<String>(&source).unwrap_or_else(|e| panic!("{}: {}", name, e));
+    assert_round_trips(&t);
+  }
+}
+
+#[test]
+fn mutated_round_trip() {
+  for (_, source) in example_grammars() {
+    // Textures have to be random to mutate them.
+    let source = source.replace("Stem", "0").replace("Leaf", "1");
+    let mut t = text::parse::<u32>(&source).unwrap();
+    let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+    for _ in 0 .. 200 {
+      mutate(&mut t, &mut rng).unwrap();
+      assert_round_trips(&t);
+    }
+  }
+}
+
+#[test]
+fn angles_round_trip() {
+  use rand::Rng;
+
+  let mut t = text::parse::<String>("S -> rotate(0) branch(Stem, 1, 1, 1, 0)\n").unwrap();
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([0x193a6754, 0xa8a7d469, 0x97830e05, 0x113ba7bb]);
+  for _ in 0 .. 10000 {
+    // Both angles that were typed in and ones that weren't, e.g. from `mutate`.
+    let degrees = rng.gen_range(-360, 360) as f32 / 4.0;
+    let radians = rng.gen_range(-10.0, 10.0);
+    for rhs in &mut t.rules[0] {
+      for action in &mut rhs.actions {
+        match action {
+          &mut alphabet::Terminal::Transform(ref mut transform) =>
+            transform.rotation = expr::Expr::Const(degrees.to_radians()),
+          &mut alphabet::Terminal::AddBranch { ref mut bend, .. } => *bend = expr::Expr::Const(radians),
+          _ => {},
+        }
+      }
+    }
+    assert_round_trips(&t);
+  }
+}
+
+#[test]
+fn parametric_angles_dont_drift() {
+  // Multiplying these by a degree and back in f32 doesn't give the same numbers.
+  let source = "S(a, b) -> rotate(a * 1.9) pitch(a / 3 + b) roll(b * 7.5) branch(Stem, 1, 1, 1, a * 3.7) S(a + 1, b)";
+  let mut t = text::parse::<String>(source).unwrap();
+  let printed = text::print(&t);
+  assert!(printed.contains("rotate(p0 * 1.9)"), "{}", printed);
+  for _ in 0 .. 5 {
+    assert_round_trips(&t);
+    t = text::parse::<String>(&text::print(&t)).unwrap();
+    assert_eq!(text::print(&t), printed);
+  }
+}
+
+#[test]
+fn empty_rule_round_trips() {
+  let t = text::parse::<String>("S -> branch(Stem, 1, 1) A B\nA\nB -(0)->\n").unwrap();
+  assert_eq!(t.rules[1].len(), 0);
+  assert_eq!(t.rules[2].len(), 1);
+  assert_round_trips(&t);
+}
+
+fn parse_error(source: &str) -> text::Error {
+  match text::parse::<String>(source) {
+    Ok(_) => panic!("{:?} parsed", source),
+    Err(e) => e,
+  }
+}
+
+#[test]
+fn undefined_nonterminal() {
+  let e = parse_error("S -> branch(Stem, 1, 1) A\nB -> rotate(10)\n");
+  assert_eq!(e.line, 1);
+  assert_eq!(e.message, "undefined nonterminal \"A\"");
+}
+
+#[test]
+fn undefined_nonterminal_in_context() {
+  let e = parse_error("S -> branch(Stem, 1, 1) A\nB < A -> rotate(10)\n");
+  assert_eq!(e.line, 2);
+  assert_eq!(e.message, "undefined nonterminal \"B\" in context");
+}
+
+#[test]
+fn wrong_arity() {
+  let e = parse_error("S -> rotate(1, 2)\n");
+  assert_eq!(e.message, "wrong number of arguments to rotate");
+  let e = parse_error("S -> A\nA -> branch(Stem, 1)\n");
+  assert_eq!(e.line, 2);
+  assert_eq!(e.message, "wrong number of arguments to branch");
+}
+
+#[test]
+fn unclosed_parenthesis() {
+  let e = parse_error("S -> rotate(30 A\nA -> branch(Stem, 1, 1)\n");
+  assert_eq!(e.line, 1);
+  assert_eq!(e.message, "unclosed parenthesis");
+}
+
+#[test]
+fn terminal_after_nonterminal() {
+  let e = parse_error("S -> A rotate(30)\nA -> branch(Stem, 1, 1)\n");
+  assert_eq!(e.line, 1);
+  assert_eq!(e.message, "terminal \"rotate\" after a nonterminal");
+}
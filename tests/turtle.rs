@@ -0,0 +1,58 @@
+extern crate lsystems;
+extern crate rand;
+
+use lsystems::*;
+
+/// Never runs short, so that only the number of iterations limits the tree.
+struct Unlimited;
+
+impl resources::ResourceModel for Unlimited {
+  type Resources = ();
+  type Shortage = ();
+
+  fn initial(&self) {}
+  fn child(&self, _: &()) {}
+  fn branch(&self, _: &mut (), _: f32, _: f32, _: f32) -> Result<(), ()> { Ok(()) }
+  fn add_child(&self, _: &mut (), _: &()) -> Result<(), ()> { Ok(()) }
+  fn pop(&self, _: &mut (), _: &()) {}
+}
+
+/// Rewrite `axiom` `iterations` times, and count the `F`s. `F` isn't rewritten, since `turtle` draws its growth by
+/// scaling rather than with more branches.
+fn textbook_branches(axiom: &str, rules: &[(char, &str)], iterations: u32) -> usize {
+  let mut s = axiom.to_string();
+  for _ in 0 .. iterations {
+    s =
+      s.chars()
+      .map(|c| rules.iter().find(|&&(d, _)| d == c).map_or(c.to_string(), |&(_, body)| body.to_string()))
+      .collect();
+  }
+  s.chars().filter(|&c| c == 'F').count()
+}
+
+fn branches(axiom: &str, rules: &[(char, &str)], iterations: u32) -> usize {
+  let branch =
+    alphabet::Terminal::AddBranch { texture_id: (), width: 0.1, length: 1.0, taper: 1.0, bend: 0.0 };
+  let t = turtle::to_grammar(axiom, rules, 25.0, &branch, iterations).unwrap();
+  let rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  word::branches(&t, 1 << 8, 1 << 20, 0.0, &Unlimited, rng).unwrap()
+    .filter(|placed| matches!(placed, &word::Placed::Branch { .. }))
+    .count()
+}
+
+#[test]
+fn binary_tree() {
+  let rules = [('X', "F[+X][-X]")];
+  for iterations in 0 .. 8 {
+    assert_eq!(branches("X", &rules, iterations), (1 << iterations) - 1);
+  }
+}
+
+#[test]
+fn fractal_plant() {
+  let rules = [('X', "F+[[X]-X]-F[-X]+FX"), ('F', "FF")];
+  let counts: Vec<usize> = (0 .. 6).map(|n| branches("X", &rules, n)).collect();
+  let expected: Vec<usize> = (0 .. 6).map(|n| textbook_branches("X", &rules[.. 1], n)).collect();
+  assert_eq!(counts, expected);
+  assert_eq!(expected, vec!(0, 3, 15, 63, 255, 1023));
+}
@@ -0,0 +1,255 @@
+extern crate cgmath;
+extern crate lsystems;
+extern crate rand;
+
+use lsystems::*;
+
+const RESOURCES: resources::NutrientsAndMass =
+  resources::NutrientsAndMass { available_nutrients: 1000.0, max_mass: 1000.0 };
+
+fn report(
+  source         : &str,
+  order          : word::Order,
+  max_recursion  : u32,
+  max_iterations : u32,
+  min_scale      : f32,
+  resources      : &resources::NutrientsAndMass,
+) -> word::Report<resources::NutrientsAndMass> {
+  let t = text::parse::<String>(source).unwrap();
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  let (_, report) =
+    word::generate_with_report(&t, order, max_recursion, max_iterations, min_scale, resources, &mut rng).unwrap();
+  report
+}
+
+/// A binary tree, with every other level just turning.
+const BINARY: &'static str = "S -> branch(Stem, 0.1, 1) A A\nA -> rotate(10) S\n";
+
+#[test]
+fn breadth_first_is_even() {
+  let report = report(BINARY, word::Order::BreadthFirst, 100, 100, 0.0, &RESOURCES);
+  assert_eq!(report.expansions, vec!(1, 2, 2, 4, 4, 8, 8, 16, 16, 32));
+}
+
+#[test]
+fn depth_first_is_lopsided() {
+  // The first child uses up the whole budget.
+  let report = report(BINARY, word::Order::DepthFirst, 100, 100, 0.0, &RESOURCES);
+  assert_eq!(report.expansions, vec!(1; 100));
+}
+
+/// A stem of branches, each nested in the one before.
+const STEM: &'static str = "S -> branch(Stem, 1, 0.1) S\n";
+
+/// What each branch of `STEM` uses.
+const NUTRIENTS: f32 = 0.1 * 0.001;
+const MASS: f32 = 0.1 * 0.02;
+
+fn assert_used(report: &word::Report<resources::NutrientsAndMass>, branches: f32) {
+  let resources = report.resources.unwrap();
+  assert!((resources.used_nutrients - branches * NUTRIENTS).abs() < 1e-6, "{:?}", resources);
+  assert!((resources.mass - branches * MASS).abs() < 1e-6, "{:?}", resources);
+}
+
+#[test]
+fn max_recursion() {
+  let report = report(STEM, word::Order::DepthFirst, 4, 100, 0.0, &RESOURCES);
+  assert_eq!(report.cutoffs.len(), 1);
+  assert_eq!(report.count(word::Cutoff::MaxRecursion), 1);
+  assert_eq!(report.expansions, vec!(1, 1, 1, 1));
+  assert_eq!(report.deepest(), Some(3));
+  assert_used(&report, 4.0);
+}
+
+#[test]
+fn max_iterations() {
+  for &order in &[word::Order::DepthFirst, word::Order::BreadthFirst] {
+    let report = report(STEM, order, 100, 3, 0.0, &RESOURCES);
+    assert_eq!(report.cutoffs.len(), 1);
+    assert_eq!(report.count(word::Cutoff::MaxIterations), 1);
+    assert_eq!(report.deepest(), Some(2));
+    assert_used(&report, 3.0);
+  }
+}
+
+#[test]
+fn min_scale() {
+  // The fifth branch is scaled by 1/16, so its expansion is cut off.
+  let source = "S -> branch(Stem, 1, 0.1) scale(0.5) S\n";
+  let report = report(source, word::Order::DepthFirst, 100, 100, 0.1, &RESOURCES);
+  assert_eq!(report.cutoffs.len(), 1);
+  assert_eq!(report.count(word::Cutoff::MinScale), 1);
+  assert_eq!(report.deepest(), Some(3));
+}
+
+#[test]
+fn no_rule() {
+  let zero_weight = "S -> branch(Stem, 1, 0.1) A\nA -(0)-> branch(Stem, 1, 0.1)\n";
+  let guarded = "S -> branch(Stem, 1, 0.1) A(1)\nA(x) : x > 1 -> branch(Stem, 1, 0.1)\n";
+  for &source in &[zero_weight, guarded] {
+    let report = report(source, word::Order::DepthFirst, 100, 100, 0.0, &RESOURCES);
+    assert_eq!(report.cutoffs.len(), 1);
+    assert_eq!(report.count(word::Cutoff::NoRule), 1);
+    assert_eq!(report.deepest(), Some(0));
+    assert_used(&report, 1.0);
+  }
+}
+
+#[test]
+fn branch_shortage() {
+  // There's only enough for one branch, so the second expansion is cut off.
+  let nutrients = resources::NutrientsAndMass { available_nutrients: 1.5 * NUTRIENTS, max_mass: 1000.0 };
+  let mass = resources::NutrientsAndMass { available_nutrients: 1000.0, max_mass: 1.5 * MASS };
+  let cases = [(nutrients, resources::Shortage::Nutrients), (mass, resources::Shortage::Mass)];
+  for &(ref resources, shortage) in &cases {
+    let report = report(STEM, word::Order::DepthFirst, 100, 100, 0.0, resources);
+    assert_eq!(report.cutoffs.len(), 1);
+    assert_eq!(report.count(word::Cutoff::Branch(shortage)), 1);
+    assert_eq!(report.deepest(), Some(0));
+    assert_used(&report, 1.0);
+  }
+}
+
+fn texture(placed: word::Placed<String>) -> String {
+  match placed {
+    word::Placed::Branch { texture_id, .. } => texture_id,
+    word::Placed::Polygon { .. } => panic!("not a branch"),
+  }
+}
+
+/// The texture of each branch `source` generates.
+fn textures(source: &str) -> Vec<String> {
+  let t = text::parse::<String>(source).unwrap();
+  let rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  word::branches(&t, 100, 100, 0.0, &RESOURCES, rng).unwrap().map(texture).collect()
+}
+
+#[test]
+fn most_specific_context() {
+  let source = "
+    S -> A B C
+    A -> branch(Stem, 0.1, 1)
+    B -> branch(Leaf, 0.1, 1)
+    A < B -> branch(Bud, 0.1, 1)
+    S / A < B > C -> branch(Flower, 0.1, 1)
+    C / A < B > C -> branch(Thorn, 0.1, 1)
+    C -> branch(Stem, 0.1, 1)
+  ";
+  assert_eq!(textures(source), vec!("Stem", "Flower", "Stem"));
+  let source = source.replace("S / A < B > C", "S / B < B > C");
+  assert_eq!(textures(&source), vec!("Stem", "Bud", "Stem"));
+  let source = source.replace("A < B ->", "C < B ->");
+  assert_eq!(textures(&source), vec!("Stem", "Leaf", "Stem"));
+}
+
+#[test]
+fn undefined_context() {
+  let mut t = text::parse::<String>("S -> A\nA -> branch(Stem, 1, 1)\n").unwrap();
+  t.rules[1][0].context.left = Some(grammar::Nonterminal(7));
+  let errors =
+    vec!(grammar::Error::UndefinedNonterminal {
+      rule        : grammar::Nonterminal(1),
+      alternative : 0,
+      nonterminal : grammar::Nonterminal(7),
+    });
+  assert_eq!(t.validate(), Err(errors.clone()));
+  let mut rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  assert_eq!(word::generate(&t, 100, 100, 0.0, &RESOURCES, &mut rng).err(), Some(errors));
+}
+
+/// The textures of a stem of 2000 branches, each picked with weights 3:1.
+fn weighted_stem(seed: [u32; 4]) -> Vec<String> {
+  let source = "S -(3)-> branch(Stem, 1, 0.001) S\nS -(1)-> branch(Leaf, 1, 0.001) S\n";
+  let t = text::parse::<String>(source).unwrap();
+  let rng: rand::XorShiftRng = rand::SeedableRng::from_seed(seed);
+  word::branches(&t, 2000, 2000, 0.0, &RESOURCES, rng).unwrap().map(texture).collect()
+}
+
+#[test]
+fn weighted_choice() {
+  let seed = [0x193a6754, 0xa8a7d469, 0x97830e05, 0x113ba7bb];
+  let textures = weighted_stem(seed);
+  assert_eq!(textures.len(), 2000);
+  let stems = textures.iter().filter(|&texture| texture == "Stem").count();
+  assert!(1400 < stems && stems < 1600, "{} of 2000", stems);
+
+  assert_eq!(weighted_stem(seed), textures);
+  assert!(weighted_stem([0x2c6fe996, 0x5d2fa4f5, 0x3b1f6a29, 0x9a3a7dbb]) != textures);
+}
+
+/// Drops every child that would make the number of branches its parent has kept a multiple of 3.
+struct DropEveryThird;
+
+impl resources::ResourceModel for DropEveryThird {
+  /// How many branches have been kept.
+  type Resources = u32;
+  type Shortage = ();
+
+  fn initial(&self) -> u32 { 0 }
+  fn child(&self, _: &u32) -> u32 { 0 }
+
+  fn branch(&self, branches: &mut u32, _: f32, _: f32, _: f32) -> Result<(), ()> {
+    *branches += 1;
+    Ok(())
+  }
+
+  fn add_child(&self, parent: &mut u32, child: &u32) -> Result<(), ()> {
+    if (*parent + *child) % 3 == 0 {
+      return Err(())
+    }
+    *parent += *child;
+    Ok(())
+  }
+
+  fn pop(&self, _: &mut u32, _: &u32) {}
+}
+
+fn apply(transform: &cgmath::Matrix4<f32>, x: f32, y: f32) -> [f32; 3] {
+  let p = transform * cgmath::Vector4::new(x, y, 0.0, 1.0);
+  [p.x, p.y, p.z]
+}
+
+fn assert_near(a: [f32; 3], b: [f32; 3]) {
+  assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-4), "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn branches_with_dropped_children() {
+  let source = "
+    S -> branch(Stem, 0.1, 1) A B
+    A -> rotate(20) polygon(Leaf, 0, 0, 0.2, 0, 0, 0.2) S
+    B -> rotate(-30) S
+  ";
+  let t = text::parse::<String>(source).unwrap();
+  let rng: rand::XorShiftRng = rand::SeedableRng::from_seed([1, 2, 3, 4]);
+  let (word, report) =
+    word::generate_with_report(&t, word::Order::DepthFirst, 12, 1000, 0.0, &DropEveryThird, &mut rng.clone())
+    .unwrap();
+  assert!(report.count(word::Cutoff::Child(())) > 10);
+
+  let branches = word::branches(&t, 12, 1000, 0.0, &DropEveryThird, rng).unwrap();
+  assert_eq!(branches.report().cutoffs, report.cutoffs);
+  assert_eq!(branches.report().expansions, report.expansions);
+  assert_eq!(branches.report().resources, report.resources);
+  let placed: Vec<word::Placed<String>> = branches.collect();
+
+  // Straight branches are prisms of 2 rings of 9 vertices, the first starting `width / 2` along the x axis.
+  let mut mesh = render_mesh(&word, &Default::default()).to_hashmap();
+  let (stems, _) = mesh.remove("Stem").unwrap();
+  let (leaves, _) = mesh.remove("Leaf").unwrap();
+  let (mut stems, mut leaves) = (stems.chunks(18), leaves.chunks(3));
+  for placed in placed {
+    match placed {
+      word::Placed::Branch { transform, width, .. } =>
+        assert_near(stems.next().unwrap()[0].position, apply(&transform, width / 2.0, 0.0)),
+      word::Placed::Polygon { transform, points, .. } => {
+        let leaf = leaves.next().unwrap();
+        for (vertex, point) in leaf.iter().zip(points.iter()) {
+          assert_near(vertex.position, apply(&transform, point[0], point[1]));
+        }
+      },
+    }
+  }
+  assert!(stems.next().is_none());
+  assert!(leaves.next().is_none());
+}